@@ -6,4 +6,4 @@ cd "$(dirname $0)"
 
 export CC=$SOLANA_LLVM_CC
 export AR=$SOLANA_LLVM_AR
-xargo build --target bpfel-unknown-unknown --release --no-default-features --features program
+xargo build --target bpfel-unknown-unknown --release --no-default-features --features program,ethash
@@ -0,0 +1,148 @@
+//! Clique (EIP-225) proof-of-authority sealing, as used by Görli and Rinkeby.
+
+#[cfg(feature = "ecrecover")]
+use arrayref::array_ref;
+use ethereum_types::{H160, H256};
+#[cfg(feature = "ecrecover")]
+use ethereum_types::U256;
+
+use crate::eth::{keccak256, BlockHeader, ExtraData};
+#[cfg(feature = "ecrecover")]
+use crate::signature::ecrecover;
+
+/// Extra data prefix reserved for signer vanity
+pub const EXTRA_VANITY: usize = 32;
+/// Extra data suffix holding the signer's secp256k1 signature
+pub const EXTRA_SEAL: usize = 65;
+/// Signers a checkpoint header may list while still fitting in `ExtraData`
+pub const MAX_SIGNERS: usize = 7;
+
+/// Blocks between checkpoints, the `clique.epoch` of the Görli and Rinkeby
+/// chain configs. Only by coincidence the same as Ethash's `EPOCH_LENGTH`.
+pub const CLIQUE_EPOCH: u64 = 30000;
+
+pub const DIFF_IN_TURN: u64 = 2;
+pub const DIFF_NO_TURN: u64 = 1;
+
+/// A signer may seal one of every `len / 2 + 1` blocks, so at most this many
+/// signers of the latest blocks need remembering.
+pub const MAX_RECENT: usize = MAX_SIGNERS / 2;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct RecentSigner {
+    pub height: u64,
+    pub signer: H160,
+}
+
+/// The authorized signers, as listed by the most recent checkpoint header,
+/// and the signers of the latest blocks.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Signers {
+    len: u8,
+    addresses: [H160; MAX_SIGNERS],
+    /// Newest first
+    recent: [RecentSigner; MAX_RECENT],
+}
+
+impl Signers {
+    pub fn as_slice(&self) -> &[H160] {
+        &self.addresses[..self.len as usize]
+    }
+
+    /// Take on the signer list of a checkpoint header, remembering the
+    /// signers of the latest blocks still.
+    pub fn update_from_checkpoint(&mut self, header: &BlockHeader) -> Option<()> {
+        let recent = self.recent;
+        *self = Self::from_checkpoint(header)?;
+        self.recent = recent;
+        Some(())
+    }
+
+    /// Whether `signer` sealed one of the `len / 2` blocks below `number`.
+    /// Blocks recorded at `number` or above are on another branch.
+    pub fn signed_recently(&self, signer: &H160, number: u64) -> bool {
+        let limit = self.len as u64 / 2 + 1;
+        self.recent
+            .iter()
+            .any(|r| r.signer == *signer && r.height < number && r.height + limit > number)
+    }
+
+    /// Record `signer` as the sealer of block `number`, in place of whatever
+    /// was recorded at that height or above.
+    pub fn record(&mut self, signer: H160, number: u64) {
+        let mut recent = [RecentSigner { height: 0, signer: H160::zero() }; MAX_RECENT];
+        recent[0] = RecentSigner { height: number, signer };
+        let older = self.recent.iter().filter(|r| r.height < number);
+        for (slot, r) in recent[1..].iter_mut().zip(older) {
+            *slot = *r;
+        }
+        self.recent = recent;
+    }
+
+    /// Read the signer list out of a checkpoint header's extra data.
+    pub fn from_checkpoint(header: &BlockHeader) -> Option<Self> {
+        if header.number % CLIQUE_EPOCH != 0 {
+            return None;
+        }
+        let extra_data = header.extra_data.as_slice();
+        if extra_data.len() < EXTRA_VANITY + EXTRA_SEAL {
+            return None;
+        }
+        let list = &extra_data[EXTRA_VANITY..extra_data.len() - EXTRA_SEAL];
+        if list.len() % 20 != 0 || list.len() / 20 > MAX_SIGNERS {
+            return None;
+        }
+        let mut signers = Signers {
+            len: (list.len() / 20) as u8,
+            addresses: [H160::zero(); MAX_SIGNERS],
+            recent: [RecentSigner { height: 0, signer: H160::zero() }; MAX_RECENT],
+        };
+        for (address, bytes) in signers.addresses.iter_mut().zip(list.chunks(20)) {
+            *address = H160::from_slice(bytes);
+        }
+        Some(signers)
+    }
+}
+
+/// Hash the signer signs: the header hash with the seal cut off the extra data.
+pub fn clique_seal_hash(header: &BlockHeader) -> Option<H256> {
+    let extra_data = header.extra_data.as_slice();
+    if extra_data.len() < EXTRA_VANITY + EXTRA_SEAL {
+        return None;
+    }
+    let mut unsealed = header.clone();
+    unsealed.extra_data = ExtraData::from_slice(&extra_data[..extra_data.len() - EXTRA_SEAL]);
+    Some(keccak256(&rlp::encode(&unsealed)))
+}
+
+#[cfg(feature = "ecrecover")]
+pub fn recover_signer(header: &BlockHeader) -> Option<H160> {
+    let hash = clique_seal_hash(header)?;
+    let extra_data = header.extra_data.as_slice();
+    let seal = &extra_data[extra_data.len() - EXTRA_SEAL..];
+    ecrecover(&hash, array_ref!(seal, 0, 64), seal[64])
+}
+
+/// The one of `signers` that sealed the header, provided its difficulty says
+/// whether it was the signer's turn. Signers take turns in the order
+/// checkpoints list them, ascending.
+#[cfg(feature = "ecrecover")]
+pub fn clique_sealer(header: &BlockHeader, signers: &[H160]) -> Option<H160> {
+    let signer = recover_signer(header)?;
+    let index = signers.iter().position(|s| *s == signer)?;
+    let in_turn = header.number % signers.len() as u64 == index as u64;
+    let difficulty = if in_turn { DIFF_IN_TURN } else { DIFF_NO_TURN };
+    if header.difficulty != U256::from(difficulty) {
+        return None;
+    }
+    Some(signer)
+}
+
+/// Check that the header was sealed by one of `signers`, with the difficulty
+/// Clique gives a block sealed in or out of turn.
+#[cfg(feature = "ecrecover")]
+pub fn verify_clique_seal(header: &BlockHeader, signers: &[H160]) -> bool {
+    clique_sealer(header, signers).is_some()
+}
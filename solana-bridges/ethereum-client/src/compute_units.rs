@@ -0,0 +1,45 @@
+//! Compute unit logging for measuring what each verification step costs.
+//! Compiled out unless the `bench-cu` feature is enabled.
+
+#[cfg(feature = "bench-cu")]
+use solana_program::{info, log::sol_log_compute_units};
+
+/// Logs `start` and the remaining compute units when created, and `end` and
+/// the remaining compute units when dropped, so every return path is covered.
+#[cfg(feature = "bench-cu")]
+pub struct ComputeUnitsSpan {
+    end: &'static str,
+}
+
+#[cfg(feature = "bench-cu")]
+impl ComputeUnitsSpan {
+    pub fn new(start: &'static str, end: &'static str) -> Self {
+        info!(start);
+        sol_log_compute_units();
+        ComputeUnitsSpan { end }
+    }
+}
+
+#[cfg(feature = "bench-cu")]
+impl Drop for ComputeUnitsSpan {
+    fn drop(&mut self) {
+        info!(self.end);
+        sol_log_compute_units();
+    }
+}
+
+/// Measure the rest of the enclosing block under `label`.
+#[cfg(feature = "bench-cu")]
+macro_rules! compute_units_span {
+    ($label:literal) => {
+        let _span = crate::compute_units::ComputeUnitsSpan::new(
+            concat!($label, " start"),
+            concat!($label, " end"),
+        );
+    };
+}
+
+#[cfg(not(feature = "bench-cu"))]
+macro_rules! compute_units_span {
+    ($label:literal) => {};
+}
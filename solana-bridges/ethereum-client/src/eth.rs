@@ -1,20 +1,181 @@
-use ethereum_types::{Bloom, H160, H256, H512, H64, U256};
+#[cfg(feature = "ethash")]
+use ethereum_types::H512;
+use ethereum_types::{Bloom, BloomInput, H160, H256, H64, U256};
+use hex_literal::hex;
 use rlp::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
 use rlp_derive::{RlpDecodable as RlpDecodableDerive, RlpEncodable as RlpEncodableDerive};
+use solana_sdk::program_error::ProgramError;
 use std::{result::Result, vec::Vec};
 
 use tiny_keccak::{Hasher, Keccak};
 
-use crate::types::*;
+use crate::clique;
+use crate::{
+    mpt::{ordered_trie_root, proves_value, verify_mpt_proof},
+    types::*,
+};
 
+/// Extra data is stored inline in every ring slot, so this bounds the slot
+/// size. Clique checkpoints carry vanity, the signer list and the seal, 237
+/// bytes with `MAX_SIGNERS` signers. Every build leaves that much room, so
+/// storage is laid out the same whichever network the build follows.
+pub const EXTRA_DATA_CAPACITY: usize =
+    clique::EXTRA_VANITY + 20 * clique::MAX_SIGNERS + clique::EXTRA_SEAL;
+
+/// Mainnet consensus caps extra data at 32 bytes.
+#[cfg(not(feature = "clique"))]
 pub const EXTRA_DATA_MAX_LEN: usize = 32;
+/// Other Clique headers than checkpoints need 97 bytes.
+#[cfg(feature = "clique")]
+pub const EXTRA_DATA_MAX_LEN: usize = EXTRA_DATA_CAPACITY;
 
 pub const EPOCH_LENGTH: u64 = 30000;
 
+/// How headers are sealed, recorded in storage by `Initialize`. Clique builds
+/// only follow Clique chains. Other builds follow mainnet from either side of
+/// the merge, going by the difficulty of the initial header, but do not cross
+/// it.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsensusMode {
+    Ethash = 1,
+    Clique = 2,
+    /// Post-merge headers, with zero difficulty and nonce and no ommers. Only
+    /// headers up to Shanghai, with at most 17 fields, can be decoded.
+    ProofOfStake = 3,
+}
+
+impl ConsensusMode {
+    /// The mode a chain starting at `header` runs in.
+    pub fn for_initial(header: &BlockHeader) -> Self {
+        if cfg!(feature = "clique") {
+            ConsensusMode::Clique
+        } else if header.difficulty.is_zero() {
+            ConsensusMode::ProofOfStake
+        } else {
+            ConsensusMode::Ethash
+        }
+    }
+
+    /// Read a stored mode. Storage holds 0 until `Initialize`, when there is
+    /// no header yet for the mode to apply to.
+    pub fn from_stored(mode: u8) -> Self {
+        match mode {
+            2 => ConsensusMode::Clique,
+            3 => ConsensusMode::ProofOfStake,
+            _ => ConsensusMode::Ethash,
+        }
+    }
+}
+
+/// A chain, told apart from others following the same consensus rules by its
+/// id and genesis block, since pre EIP-155 headers carry neither.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Network {
+    pub chain_id: u64,
+    pub genesis_hash: H256,
+}
+
+impl Network {
+    /// What `Initialize` records, so storage set up for one network is
+    /// refused by builds following another.
+    pub fn fingerprint(&self) -> H256 {
+        let mut data = [0u8; 40];
+        data[..8].copy_from_slice(&self.chain_id.to_be_bytes());
+        data[8..].copy_from_slice(self.genesis_hash.as_bytes());
+        keccak256(&data)
+    }
+}
+
+pub const MAINNET: Network = Network {
+    chain_id: 1,
+    genesis_hash: H256(hex!("d4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3")),
+};
+
+pub const GOERLI: Network = Network {
+    chain_id: 5,
+    genesis_hash: H256(hex!("bf7e331f7f7c1dd2e05159666b3bf8bc7a8a3a9eb1d518969eab529dd9b88c1a")),
+};
+
+/// The network this build follows
+#[cfg(not(feature = "clique"))]
+pub const NETWORK: Network = MAINNET;
+#[cfg(feature = "clique")]
+pub const NETWORK: Network = GOERLI;
+
+/// `uncles_hash` of a block without ommers, the hash of an empty RLP list
+pub const EMPTY_UNCLES_HASH: H256 = H256([
+    0x1d, 0xcc, 0x4d, 0xe8, 0xde, 0xc7, 0x5d, 0x7a, 0xab, 0x85, 0xb5, 0x67, 0xb6, 0xcc, 0xd4, 0x1a,
+    0xd3, 0x12, 0x45, 0x1b, 0x94, 0x8a, 0x74, 0x13, 0xf0, 0xa1, 0x42, 0xfd, 0x40, 0xd4, 0x93, 0x47,
+]);
+
+/// Block hashes pinned by height. A header at one of these heights must have
+/// the pinned hash, so only chains passing through every checkpoint are
+/// accepted. Operators wanting this add entries and rebuild.
+pub const CHECKPOINTS: &[(u64, H256)] = &[];
+
+/// An extra check a hard fork makes on the headers of a range of heights
+pub struct HardforkRule {
+    pub from_height: u64,
+    /// Inclusive
+    pub to_height: u64,
+    pub check: fn(&BlockHeader) -> bool,
+}
+
+pub const DAO_FORK_BLOCK: u64 = 1_920_000;
+/// Blocks from the DAO fork on that must carry `DAO_FORK_EXTRA_DATA`
+pub const DAO_FORK_EXTRA_RANGE: u64 = 10;
+pub const DAO_FORK_EXTRA_DATA: &[u8] = b"dao-hard-fork";
+
+pub fn has_dao_fork_extra_data(header: &BlockHeader) -> bool {
+    header.extra_data.as_slice() == DAO_FORK_EXTRA_DATA
+}
+
+#[cfg(not(feature = "clique"))]
+pub const HARDFORK_RULES: &[HardforkRule] = &[HardforkRule {
+    from_height: DAO_FORK_BLOCK,
+    to_height: DAO_FORK_BLOCK + DAO_FORK_EXTRA_RANGE - 1,
+    check: has_dao_fork_extra_data,
+}];
+#[cfg(feature = "clique")]
+pub const HARDFORK_RULES: &[HardforkRule] = &[];
+
+pub const MINIMUM_DIFFICULTY: u64 = 131072;
+pub const DIFFICULTY_BOUND_DIVISOR: u64 = 2048;
+pub const EXP_DIFF_PERIOD: u64 = 100000;
+/// Frontier raises difficulty for blocks mined faster than this many seconds
+pub const FRONTIER_DURATION_LIMIT: u64 = 13;
+
+/// How far ahead of the Solana clock a header's timestamp may be, in seconds
+pub const MAX_FUTURE_DRIFT: u64 = 15;
+
+pub const MIN_GAS_LIMIT: u64 = 5000;
+pub const GAS_LIMIT_BOUND_DIVISOR: u64 = 1024;
+/// EIP-1559 doubles the gas limit target at London
+pub const ELASTICITY_MULTIPLIER: u64 = 2;
+
+pub const HOMESTEAD_BLOCK: u64 = 1_150_000;
+pub const BYZANTIUM_BLOCK: u64 = 4_370_000;
+
+/// Mainnet difficulty bomb delays, as (fork block, fake block number offset)
+/// in activation order.
+pub const BOMB_DELAYS: [(u64, u64); 6] = [
+    (BYZANTIUM_BLOCK, 3_000_000),
+    (7_280_000, 5_000_000), // Constantinople
+    (9_200_000, 9_000_000), // Muir Glacier
+    (12_965_000, 9_700_000), // London
+    (13_773_000, 10_700_000), // Arrow Glacier
+    (15_050_000, 11_400_000), // Gray Glacier
+];
+
+// The length is stored in a byte, so the maximum must fit in one
+const _: [(); 0] = [(); (EXTRA_DATA_CAPACITY > std::u8::MAX as usize) as usize];
+const _: [(); 0] = [(); (EXTRA_DATA_MAX_LEN > EXTRA_DATA_CAPACITY) as usize];
+
 #[derive(Debug, Clone, Copy)]
 pub struct ExtraData {
     len: u8,
-    bytes: [u8; EXTRA_DATA_MAX_LEN],
+    bytes: [u8; EXTRA_DATA_CAPACITY],
 }
 
 #[derive(Debug, Eq, PartialEq, Clone)]
@@ -34,16 +195,111 @@ pub struct BlockHeader {
     pub extra_data: ExtraData,
     pub mix_hash: H256,
     pub nonce: H64,
+    /// EIP-1559, present from London onwards
+    pub base_fee_per_gas: Option<U256>,
+    /// EIP-4895, present from Shanghai onwards
+    pub withdrawals_root: Option<H256>,
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, RlpEncodableDerive, RlpDecodableDerive)]
+#[derive(Debug, Eq, PartialEq, Clone)]
 pub struct Receipt {
+    /// EIP-2718 transaction type, 0 for a legacy receipt
+    pub tx_type: u8,
     pub status: bool,
-    pub gas_used: U256,
+    pub cumulative_gas_used: U256,
     pub log_bloom: Bloom,
     pub logs: Vec<LogEntry>,
 }
 
+impl Receipt {
+    /// The receipt as stored in the receipt trie: the RLP list, prefixed by
+    /// the transaction type unless it is a legacy receipt.
+    pub fn envelope(&self) -> Vec<u8> {
+        let mut stream = RlpStream::new();
+        self.append_fields(&mut stream);
+        let fields = stream.out();
+        if self.tx_type == 0 {
+            return fields;
+        }
+        let mut envelope = Vec::with_capacity(1 + fields.len());
+        envelope.push(self.tx_type);
+        envelope.extend_from_slice(&fields);
+        envelope
+    }
+
+    fn append_fields(&self, stream: &mut RlpStream) {
+        stream.begin_list(4);
+        stream.append(&self.status);
+        stream.append(&self.cumulative_gas_used);
+        stream.append(&self.log_bloom);
+        stream.append_list(&self.logs);
+    }
+
+    fn decode_fields(tx_type: u8, rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(Receipt {
+            tx_type,
+            status: rlp.val_at(0)?,
+            cumulative_gas_used: rlp.val_at(1)?,
+            log_bloom: rlp.val_at(2)?,
+            logs: rlp.list_at(3)?,
+        })
+    }
+}
+
+/// A typed receipt is either its bare envelope, as in the receipt trie, or
+/// that envelope wrapped in a byte string, as within an RLP list.
+impl Decodable for Receipt {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        if rlp.is_list() {
+            return Receipt::decode_fields(0, rlp);
+        }
+        let envelope = match rlp.as_raw().first() {
+            Some(&tx_type) if tx_type < 0x80 => rlp.as_raw(),
+            _ => rlp.data()?,
+        };
+        match envelope.split_first() {
+            Some((&tx_type, fields)) if tx_type != 0 && tx_type < 0x80 => {
+                Receipt::decode_fields(tx_type, &Rlp::new(fields))
+            }
+            _ => Err(DecoderError::RlpExpectedToBeList),
+        }
+    }
+}
+
+impl Encodable for Receipt {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        if self.tx_type == 0 {
+            self.append_fields(stream);
+        } else {
+            // The enclosing `append` counts the item, so encode the value directly
+            self.envelope().as_slice().rlp_append(stream);
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, RlpEncodableDerive, RlpDecodableDerive)]
+pub struct AccountState {
+    pub nonce: u64,
+    pub balance: U256,
+    pub storage_root: H256,
+    pub code_hash: H256,
+}
+
+impl AccountState {
+    /// The state of an address that is not in the state trie
+    pub fn empty() -> Self {
+        AccountState {
+            nonce: 0,
+            balance: U256::zero(),
+            storage_root: keccak256(&rlp::NULL_RLP),
+            code_hash: keccak256(&[]),
+        }
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct LogEntry {
     pub address: H160,
@@ -85,9 +341,13 @@ pub enum TransactionAction {
 
 impl Encodable for TransactionAction {
     fn rlp_append(&self, stream: &mut RlpStream) {
+        // The enclosing `append` counts the item, so encode the value directly
         match self {
-            &TransactionAction::Call(address) => stream.append(&address),
-            &TransactionAction::Create => stream.begin_list(0),
+            &TransactionAction::Call(address) => address.rlp_append(stream),
+            &TransactionAction::Create => {
+                let empty: &[u8] = &[];
+                empty.rlp_append(stream)
+            }
         };
     }
 }
@@ -148,16 +408,204 @@ impl Decodable for Transaction {
     }
 }
 
+#[derive(RlpEncodableDerive, RlpDecodableDerive)]
+pub struct AccessListItem {
+    pub address: H160,
+    pub storage_keys: Vec<H256>,
+}
+
+/// EIP-2930 transaction, type 0x01
+pub struct AccessListTransaction {
+    pub chain_id: Scalar,
+    pub nonce: Scalar,
+    pub gas_price: Scalar,
+    pub gas_limit: Scalar,
+    pub to: TransactionAction,
+    pub value: Scalar,
+    pub data: TransactionData,
+    pub access_list: Vec<AccessListItem>,
+    pub y_parity: U256,
+    pub r: U256,
+    pub s: U256,
+}
+
+impl Encodable for AccessListTransaction {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(11);
+        stream.append(&self.chain_id);
+        stream.append(&self.nonce);
+        stream.append(&self.gas_price);
+        stream.append(&self.gas_limit);
+        stream.append(&self.to);
+        stream.append(&self.value);
+        stream.append(&self.data.bytes);
+        stream.append_list(&self.access_list);
+        stream.append(&self.y_parity);
+        stream.append(&self.r);
+        stream.append(&self.s);
+    }
+}
+
+impl Decodable for AccessListTransaction {
+    fn decode(serialized: &Rlp) -> Result<Self, DecoderError> {
+        let res = AccessListTransaction {
+            chain_id: serialized.val_at(0)?,
+            nonce: serialized.val_at(1)?,
+            gas_price: serialized.val_at(2)?,
+            gas_limit: serialized.val_at(3)?,
+            to: serialized.val_at(4)?,
+            value: serialized.val_at(5)?,
+            data: TransactionData {
+                bytes: serialized.val_at(6)?,
+            },
+            access_list: serialized.list_at(7)?,
+            y_parity: serialized.val_at(8)?,
+            r: serialized.val_at(9)?,
+            s: serialized.val_at(10)?,
+        };
+        return Ok(res);
+    }
+}
+
+/// EIP-1559 transaction, type 0x02
+pub struct DynamicFeeTransaction {
+    pub chain_id: Scalar,
+    pub nonce: Scalar,
+    pub max_priority_fee_per_gas: Scalar,
+    pub max_fee_per_gas: Scalar,
+    pub gas_limit: Scalar,
+    pub to: TransactionAction,
+    pub value: Scalar,
+    pub data: TransactionData,
+    pub access_list: Vec<AccessListItem>,
+    pub y_parity: U256,
+    pub r: U256,
+    pub s: U256,
+}
+
+impl Encodable for DynamicFeeTransaction {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(12);
+        stream.append(&self.chain_id);
+        stream.append(&self.nonce);
+        stream.append(&self.max_priority_fee_per_gas);
+        stream.append(&self.max_fee_per_gas);
+        stream.append(&self.gas_limit);
+        stream.append(&self.to);
+        stream.append(&self.value);
+        stream.append(&self.data.bytes);
+        stream.append_list(&self.access_list);
+        stream.append(&self.y_parity);
+        stream.append(&self.r);
+        stream.append(&self.s);
+    }
+}
+
+impl Decodable for DynamicFeeTransaction {
+    fn decode(serialized: &Rlp) -> Result<Self, DecoderError> {
+        let res = DynamicFeeTransaction {
+            chain_id: serialized.val_at(0)?,
+            nonce: serialized.val_at(1)?,
+            max_priority_fee_per_gas: serialized.val_at(2)?,
+            max_fee_per_gas: serialized.val_at(3)?,
+            gas_limit: serialized.val_at(4)?,
+            to: serialized.val_at(5)?,
+            value: serialized.val_at(6)?,
+            data: TransactionData {
+                bytes: serialized.val_at(7)?,
+            },
+            access_list: serialized.list_at(8)?,
+            y_parity: serialized.val_at(9)?,
+            r: serialized.val_at(10)?,
+            s: serialized.val_at(11)?,
+        };
+        return Ok(res);
+    }
+}
+
+/// A transaction as found in a block body. EIP-2718 typed transactions are
+/// byte strings holding the type byte followed by the RLP payload, legacy
+/// ones are plain RLP lists.
+pub enum TypedTransaction {
+    Legacy(Transaction),
+    AccessList(AccessListTransaction),
+    DynamicFee(DynamicFeeTransaction),
+}
+
+impl TypedTransaction {
+    pub const ACCESS_LIST_TYPE: u8 = 0x01;
+    pub const DYNAMIC_FEE_TYPE: u8 = 0x02;
+
+    /// The transaction as stored in the transaction trie: the RLP of a legacy
+    /// transaction, or the type followed by the RLP of a typed one.
+    pub fn envelope(&self) -> Vec<u8> {
+        let (tx_type, payload) = match self {
+            TypedTransaction::Legacy(tx) => return rlp::encode(tx),
+            TypedTransaction::AccessList(tx) => (Self::ACCESS_LIST_TYPE, rlp::encode(tx)),
+            TypedTransaction::DynamicFee(tx) => (Self::DYNAMIC_FEE_TYPE, rlp::encode(tx)),
+        };
+        let mut envelope = Vec::with_capacity(1 + payload.len());
+        envelope.push(tx_type);
+        envelope.extend_from_slice(&payload);
+        envelope
+    }
+}
+
+impl Encodable for TypedTransaction {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        match self {
+            TypedTransaction::Legacy(tx) => tx.rlp_append(stream),
+            // The enclosing `append` counts the item, so encode the value directly
+            _ => self.envelope().as_slice().rlp_append(stream),
+        }
+    }
+}
+
+impl Decodable for TypedTransaction {
+    fn decode(serialized: &Rlp) -> Result<Self, DecoderError> {
+        if serialized.is_list() {
+            return Ok(TypedTransaction::Legacy(serialized.as_val()?));
+        }
+        match serialized.data()?.split_first() {
+            Some((&Self::ACCESS_LIST_TYPE, payload)) => {
+                Ok(TypedTransaction::AccessList(rlp::decode(payload)?))
+            }
+            Some((&Self::DYNAMIC_FEE_TYPE, payload)) => {
+                Ok(TypedTransaction::DynamicFee(rlp::decode(payload)?))
+            }
+            _ => Err(DecoderError::Custom("Unknown transaction type")),
+        }
+    }
+}
+
+/// Most transactions a block may carry before decoding it is refused, so a
+/// crafted block cannot exhaust the heap. A full 30M gas block of plain
+/// transfers holds about 1430.
+pub const MAX_TXS_PER_BLOCK: usize = 2048;
+
 pub struct Block {
     pub header: BlockHeader,
-    pub transactions: Vec<Transaction>,
+    pub transactions: Vec<TypedTransaction>,
+    pub uncles: Vec<BlockHeader>,
 }
 
+/// A block is the list of its header, transactions and uncles. A header alone
+/// is a list too, so check the shape rather than letting it be misread.
 impl Decodable for Block {
     fn decode(serialized: &Rlp) -> Result<Self, DecoderError> {
+        if serialized.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        if !serialized.at(0)?.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        if serialized.at(1)?.item_count()? > MAX_TXS_PER_BLOCK {
+            return Err(DecoderError::Custom("too many transactions in block"));
+        }
         let res = Block {
             header: serialized.val_at(0)?,
             transactions: serialized.list_at(1)?,
+            uncles: serialized.list_at(2)?,
         };
         return Ok(res);
     }
@@ -165,18 +613,129 @@ impl Decodable for Block {
 
 impl Encodable for Block {
     fn rlp_append(&self, stream: &mut RlpStream) {
-        stream.begin_list(2);
+        stream.begin_list(3);
         stream.append(&self.header);
         stream.append_list(&self.transactions);
+        stream.append_list(&self.uncles);
     }
 }
 
+fn fixed_at<'a>(serialized: &Rlp<'a>, index: usize, len: usize) -> Result<&'a [u8], DecoderError> {
+    let data = serialized.at(index)?.data()?;
+    match data.len() {
+        l if l < len => Err(DecoderError::RlpIsTooShort),
+        l if l > len => Err(DecoderError::RlpIsTooBig),
+        _ => Ok(data),
+    }
+}
+
+fn uint_at<'a>(serialized: &Rlp<'a>, index: usize, max_len: usize) -> Result<&'a [u8], DecoderError> {
+    let item = serialized.at(index)?;
+    if !item.is_data() {
+        return Err(DecoderError::RlpExpectedToBeData);
+    }
+    let data = item.data()?;
+    if data.len() > max_len {
+        return Err(DecoderError::RlpIsTooBig);
+    }
+    if data.first() == Some(&0) {
+        return Err(DecoderError::RlpInvalidIndirection);
+    }
+    Ok(data)
+}
+
+fn u64_at(serialized: &Rlp, index: usize) -> Result<u64, DecoderError> {
+    Ok(uint_at(serialized, index, 8)?
+        .iter()
+        .fold(0, |acc, &b| (acc << 8) | b as u64))
+}
+
+fn u256_at(serialized: &Rlp, index: usize) -> Result<U256, DecoderError> {
+    Ok(U256::from_big_endian(uint_at(serialized, index, 32)?))
+}
+
+fn decode_header_fields(serialized: &Rlp) -> Result<BlockHeader, DecoderError> {
+    let optional_fields = BlockHeader::optional_rlp_fields(serialized)?;
+    let extra_data = ExtraData::from_slice_checked(serialized.at(12)?.data()?).ok_or(DecoderError::RlpIsTooBig)?;
+    Ok(BlockHeader {
+        parent_hash: H256::from_slice(fixed_at(serialized, 0, 32)?),
+        uncles_hash: H256::from_slice(fixed_at(serialized, 1, 32)?),
+        author: H160::from_slice(fixed_at(serialized, 2, 20)?),
+        state_root: H256::from_slice(fixed_at(serialized, 3, 32)?),
+        transactions_root: H256::from_slice(fixed_at(serialized, 4, 32)?),
+        receipts_root: H256::from_slice(fixed_at(serialized, 5, 32)?),
+        log_bloom: Bloom::from_slice(fixed_at(serialized, 6, 256)?),
+        difficulty: u256_at(serialized, 7)?,
+        number: u64_at(serialized, 8)?,
+        gas_limit: u256_at(serialized, 9)?,
+        gas_used: u256_at(serialized, 10)?,
+        timestamp: u64_at(serialized, 11)?,
+        extra_data,
+        mix_hash: H256::from_slice(fixed_at(serialized, 13, 32)?),
+        nonce: H64::from_slice(fixed_at(serialized, 14, 8)?),
+        base_fee_per_gas: if optional_fields >= 1 {
+            Some(u256_at(serialized, BlockHeader::NUM_FIELDS)?)
+        } else {
+            None
+        },
+        withdrawals_root: if optional_fields >= 2 {
+            Some(H256::from_slice(fixed_at(serialized, BlockHeader::NUM_FIELDS + 1, 32)?))
+        } else {
+            None
+        },
+    })
+}
+
+/// Reject a header whose RLP decodes but is not how the header encodes, such
+/// as an integer with a needless length prefix. Block hashes are taken over
+/// the canonical encoding, so the submitted bytes must be exactly that.
+pub(crate) fn check_header_rlp_canonical(bytes: &[u8], header: &BlockHeader) -> Result<(), DecoderError> {
+    if rlp::encode(header) != bytes {
+        return Err(DecoderError::Custom("non-canonical header RLP"));
+    }
+    Ok(())
+}
+
+/// Check the block's ommers against the `uncles_hash` committed to in its header.
+pub fn verify_uncles_hash(block: &Block) -> bool {
+    keccak256(&rlp::encode_list(&block.uncles)) == block.header.uncles_hash
+}
+
+/// Root of the trie of `transactions`, each stored as in a block: a legacy
+/// transaction as its RLP, a typed one as its envelope.
+pub fn compute_transactions_root(transactions: &[TypedTransaction]) -> H256 {
+    ordered_trie_root(transactions.iter().map(TypedTransaction::envelope))
+}
+
+/// Check the block's transactions are exactly those committed to by the
+/// `transactions_root` in its header, none left out or added.
+pub fn verify_transactions_root(block: &Block) -> bool {
+    compute_transactions_root(&block.transactions) == block.header.transactions_root
+}
+
 pub fn hash_header(header: &BlockHeader, truncated: bool) -> H256 {
     let mut stream = RlpStream::new();
     header.stream_rlp(&mut stream, truncated);
     return keccak256(stream.out().as_slice());
 }
 
+/// The block hash, over the whole header.
+pub fn block_hash(header: &BlockHeader) -> H256 {
+    hash_header(header, false)
+}
+
+/// The block hash, straight from the header's RLP as received, without
+/// decoding it. The bytes must be exactly one header.
+pub fn hash_rlp_header(rlp_bytes: &[u8]) -> H256 {
+    keccak256(rlp_bytes)
+}
+
+/// Hash of the header without its seal (mix hash and nonce), which is what
+/// Ethash commits to.
+pub fn seal_hash(header: &BlockHeader) -> H256 {
+    hash_header(header, true)
+}
+
 pub fn keccak256(bytes: &[u8]) -> H256 {
     let mut keccak256 = Keccak::v256();
     let mut out = [0u8; 32];
@@ -185,52 +744,361 @@ pub fn keccak256(bytes: &[u8]) -> H256 {
     H256::from(out)
 }
 
-pub fn verify_block(header: &BlockHeader, parent: Option<&BlockHeader>) -> Result<(), CustomError> {
-    use CustomError::*;
+/// `parent` comes with its hash, which storage keeps alongside each header.
+/// Without a parent, as at `Initialize`, a zero `parent_hash` is only allowed
+/// on the genesis block.
+pub fn verify_block(
+    mode: ConsensusMode,
+    header: &BlockHeader,
+    parent: Option<(&BlockHeader, &H256)>,
+) -> Result<(), BridgeError> {
+    use BridgeError::*;
+    compute_units_span!("verify_block");
+
+    if mode == ConsensusMode::ProofOfStake {
+        if !header.difficulty.is_zero() {
+            return Err(VerifyHeaderFailed_InvalidDifficulty);
+        }
+        if !header.nonce.is_zero() || header.uncles_hash != EMPTY_UNCLES_HASH {
+            return Err(VerifyHeaderFailed_InvalidProofOfStake);
+        }
+    }
 
-    if let Some(p) = parent {
+    if let Some((p, parent_hash)) = parent {
         if header.number != p.number + 1 {
             return Err(VerifyHeaderFailed_NonConsecutiveHeight);
         }
         if header.timestamp <= p.timestamp {
             return Err(VerifyHeaderFailed_NonMonotonicTimestamp);
         }
-        if header.parent_hash != hash_header(p, false) {
+        if header.parent_hash != *parent_hash {
             return Err(VerifyHeaderFailed_InvalidParentHash);
         }
+        // Clique difficulty only marks in-turn signing, checked with the seal
+        if mode == ConsensusMode::Ethash
+            && header.difficulty != expected_difficulty(p, header.timestamp)
+        {
+            return Err(VerifyHeaderFailed_InvalidDifficulty);
+        }
+        if !check_gas_limit(p, header) {
+            return Err(VerifyHeaderFailed_InvalidGasLimit);
+        }
+    } else if header.parent_hash.is_zero() && header.number != 0 {
+        // Only genesis has no parent
+        return Err(VerifyHeaderFailed_InvalidParentHash);
     };
 
-    if header.extra_data.bytes.len() > 32 {
+    if header.gas_used > header.gas_limit {
+        return Err(VerifyHeaderFailed_GasUsedOverLimit);
+    }
+    if header.extra_data.as_slice().len() > EXTRA_DATA_MAX_LEN {
         return Err(VerifyHeaderFailed_TooMuchExtraData);
     }
 
+    verify_hardfork_rules(HARDFORK_RULES, header)?;
+    verify_checkpoint(CHECKPOINTS, header)
+}
+
+/// Check `header` against every rule in `rules` covering its height.
+pub fn verify_hardfork_rules(rules: &[HardforkRule], header: &BlockHeader) -> Result<(), BridgeError> {
+    let applies = |rule: &&HardforkRule| (rule.from_height..=rule.to_height).contains(&header.number);
+    if rules.iter().filter(applies).all(|rule| (rule.check)(header)) {
+        Ok(())
+    } else {
+        Err(BridgeError::VerifyHeaderFailed_HardforkRule)
+    }
+}
+
+/// Check `header` against the hash `checkpoints` pins at its height, if any.
+pub fn verify_checkpoint(checkpoints: &[(u64, H256)], header: &BlockHeader) -> Result<(), BridgeError> {
+    match checkpoints.iter().find(|&&(height, _)| height == header.number) {
+        Some((_, expected)) if block_hash(header) != *expected => {
+            Err(BridgeError::VerifyHeaderFailed_CheckpointMismatch)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// `verify_block` for relayers holding the parent header but not its hash,
+/// to check a header before submitting it, in the mode a chain starting at
+/// `parent` would run in. Nothing else is checked, so a header passes exactly
+/// when `NewBlock` would take it on top of `parent`, storage aside.
+pub fn validate_header(parent: &BlockHeader, child: &BlockHeader) -> Result<(), BridgeError> {
+    compute_units_span!("validate_header");
+    let mode = ConsensusMode::for_initial(parent);
+    verify_block(mode, child, Some((parent, &block_hash(parent))))
+}
+
+/// Whether the two headers are alternatives for the same block: the same
+/// height on the same parent.
+pub fn are_siblings(a: &BlockHeader, b: &BlockHeader) -> bool {
+    a.parent_hash == b.parent_hash && a.number == b.number
+}
+
+/// Check that the gas limit moved by less than 1/1024 of the parent's and
+/// stays above the minimum.
+pub fn check_gas_limit(parent: &BlockHeader, child: &BlockHeader) -> bool {
+    let parent_gas_limit = match (&parent.base_fee_per_gas, &child.base_fee_per_gas) {
+        (None, Some(_)) => parent.gas_limit * ELASTICITY_MULTIPLIER,
+        _ => parent.gas_limit,
+    };
+    let limit = parent_gas_limit / GAS_LIMIT_BOUND_DIVISOR;
+    let diff = if child.gas_limit > parent_gas_limit {
+        child.gas_limit - parent_gas_limit
+    } else {
+        parent_gas_limit - child.gas_limit
+    };
+    diff < limit && child.gas_limit >= U256::from(MIN_GAS_LIMIT)
+}
+
+/// Reject headers timestamped more than `MAX_FUTURE_DRIFT` seconds after
+/// `now`, a unix timestamp.
+pub fn verify_timestamp_drift(header: &BlockHeader, now: i64) -> Result<(), BridgeError> {
+    if header.timestamp > (now.max(0) as u64).saturating_add(MAX_FUTURE_DRIFT) {
+        return Err(BridgeError::VerifyHeaderFailed_TooFarInFuture);
+    }
     Ok(())
 }
 
+/// Difficulty a child of `parent` mined at `header_timestamp` must have, following
+/// the mainnet Frontier, Homestead and Byzantium (with later bomb delays) rules.
+pub fn expected_difficulty(parent: &BlockHeader, header_timestamp: u64) -> U256 {
+    let number = parent.number + 1;
+    let adjustment = parent.difficulty / DIFFICULTY_BOUND_DIVISOR;
+    let elapsed = header_timestamp.saturating_sub(parent.timestamp);
+
+    let mut difficulty = if number < HOMESTEAD_BLOCK {
+        if elapsed < FRONTIER_DURATION_LIMIT {
+            parent.difficulty + adjustment
+        } else {
+            parent.difficulty - adjustment
+        }
+    } else {
+        // parent_diff + parent_diff / 2048 * max(y - elapsed / divisor, -99)
+        let (y, divisor) = if number < BYZANTIUM_BLOCK {
+            (1, 10)
+        } else if parent.uncles_hash == EMPTY_UNCLES_HASH {
+            (1, 9)
+        } else {
+            (2, 9)
+        };
+        let step = elapsed / divisor;
+        if step <= y {
+            parent.difficulty + adjustment * (y - step)
+        } else {
+            parent.difficulty - adjustment * std::cmp::min(step - y, 99)
+        }
+    };
+
+    difficulty = std::cmp::max(difficulty, U256::from(MINIMUM_DIFFICULTY));
+
+    // The bomb is computed from a fake block number pushed back by the delay
+    let delay = BOMB_DELAYS
+        .iter()
+        .rev()
+        .find(|&&(fork, _)| number >= fork)
+        .map_or(0, |&(_, delay)| delay);
+    let period_count = number.saturating_sub(delay) / EXP_DIFF_PERIOD;
+    if period_count > 1 {
+        difficulty = difficulty + (U256::one() << (period_count - 2) as usize);
+    }
+
+    difficulty
+}
+
+/// Check that `expected_receipt` is stored under `key` (the RLP of the
+/// transaction index) in the receipt trie with root `receipts_root`.
+pub fn verify_receipt_proof(
+    receipts_root: H256,
+    key: &[u8],
+    proof: &[Vec<u8>],
+    expected_receipt: &[u8],
+) -> bool {
+    proves_value(receipts_root, key, proof, expected_receipt)
+}
+
+/// The block bloom is the union of its receipts' blooms.
+pub fn compute_block_bloom(receipts: &[Receipt]) -> Bloom {
+    let mut bloom = Bloom::zero();
+    for receipt in receipts {
+        bloom.accrue_bloom(&receipt.log_bloom);
+    }
+    bloom
+}
+
+/// Check `receipts` account for every bit of the header's bloom. Together with
+/// a proof of each, this shows no receipt with logs was left out.
+pub fn verify_block_bloom(header: &BlockHeader, receipts: &[Receipt]) -> bool {
+    compute_block_bloom(receipts) == header.log_bloom
+}
+
+/// Whether `item`, a log address or topic, may have been logged under `bloom`:
+/// the three bits picked by its Keccak-256 hash must be set. False positives
+/// are possible, false negatives are not.
+pub fn bloom_may_contain(bloom: &Bloom, item: &[u8]) -> bool {
+    bloom.contains_input(BloomInput::Raw(item))
+}
+
+/// Look up `address` in the state trie with root `state_root`.
+pub fn verify_account_proof(
+    state_root: H256,
+    address: H160,
+    proof: &[Vec<u8>],
+) -> Result<AccountState, BridgeError> {
+    Ok(find_account(state_root, address, proof)?.unwrap_or_else(AccountState::empty))
+}
+
+/// Like `verify_account_proof`, but telling an address the proof shows is
+/// not in the state trie apart from one holding an empty account.
+pub fn find_account(
+    state_root: H256,
+    address: H160,
+    proof: &[Vec<u8>],
+) -> Result<Option<AccountState>, BridgeError> {
+    let key = keccak256(address.as_bytes());
+    match verify_mpt_proof(state_root, key.as_bytes(), proof) {
+        Ok(Some(account)) => rlp::decode(&account)
+            .map(Some)
+            .map_err(|_| BridgeError::InvalidProof_BadMerkle),
+        Ok(None) => Ok(None),
+        Err(_) => Err(BridgeError::InvalidProof_BadMerkle),
+    }
+}
+
+/// Look up `slot` in the storage trie with root `storage_root`. Absent slots
+/// read as zero.
+pub fn verify_storage_proof(
+    storage_root: H256,
+    slot: H256,
+    proof: &[Vec<u8>],
+) -> Result<H256, BridgeError> {
+    let key = keccak256(slot.as_bytes());
+    let value = verify_mpt_proof(storage_root, key.as_bytes(), proof)
+        .map_err(|_| BridgeError::InvalidProof_BadMerkle)?;
+    let mut word = H256::zero();
+    if let Some(value) = value {
+        let bytes = Rlp::new(&value)
+            .data()
+            .map_err(|_| BridgeError::InvalidProof_BadMerkle)?;
+        if bytes.len() > 32 {
+            return Err(BridgeError::InvalidProof_BadMerkle);
+        }
+        word.0[32 - bytes.len()..].copy_from_slice(bytes);
+    }
+    Ok(word)
+}
+
 pub fn height_to_epoch(h: u64) -> u64 {
     h / EPOCH_LENGTH
 }
 
+#[cfg(feature = "ethash")]
 pub fn verify_pow<F>(header: &BlockHeader, lookup: F) -> bool
 where
     F: FnMut(u32) -> H512,
 {
     use ethash::*;
+    compute_units_span!("verify_pow");
     let epoch = height_to_epoch(header.number) as usize;
     let full_size = get_full_size(epoch);
 
     let (_mix_hash, result) =
-        hashimoto(hash_header(&header, true), header.nonce, full_size, lookup);
+        hashimoto(seal_hash(header), header.nonce, full_size, lookup);
     let target = cross_boundary(header.difficulty);
 
     return U256::from_big_endian(result.as_fixed_bytes()) <= target;
 }
 
+/// Cheap pre-filter for `verify_pow`, touching no dataset elements. Ethash's
+/// final hash is computed from the seal hash, the nonce and the claimed mix
+/// hash alone, so we can check that it meets the difficulty target. This
+/// rejects zeroed or tampered seals, but not a header whose mix hash was
+/// ground out without the dataset: that takes as much work as mining, yet
+/// only `verify_pow` shows the mix hash is the one the dataset yields.
+#[cfg(feature = "ethash")]
+pub fn quick_pow_sanity(header: &BlockHeader) -> bool {
+    if header.mix_hash.is_zero() || header.nonce.is_zero() {
+        return false;
+    }
+
+    let mut seed = [0u8; 64];
+    let mut keccak512 = Keccak::v512();
+    keccak512.update(seal_hash(header).as_bytes());
+    let mut nonce = header.nonce.to_fixed_bytes();
+    nonce.reverse();
+    keccak512.update(&nonce);
+    keccak512.finalize(&mut seed);
+
+    let mut keccak256 = Keccak::v256();
+    let mut result = [0u8; 32];
+    keccak256.update(&seed);
+    keccak256.update(header.mix_hash.as_bytes());
+    keccak256.finalize(&mut result);
+
+    U256::from_big_endian(&result) <= ethash::cross_boundary(header.difficulty)
+}
+
+/// The Ethash cache for one epoch, from which any dataset item of that epoch
+/// can be recomputed. Building it is far too expensive for the program itself,
+/// which is handed the accessed dataset elements instead, so this is for
+/// relayers and tests checking many headers of the same epoch.
+#[cfg(feature = "ethash")]
+pub struct PowContext {
+    pub epoch: u64,
+    cache: Vec<u8>,
+}
+
+#[cfg(feature = "ethash")]
+pub fn build_pow_context(epoch: u64) -> PowContext {
+    use ethash::*;
+    let mut cache = vec![0; get_cache_size(epoch as usize)];
+    make_cache(&mut cache, get_seedhash(epoch as usize));
+    PowContext { epoch, cache }
+}
+
+#[cfg(feature = "ethash")]
+impl PowContext {
+    pub fn dataset_item(&self, index: u32) -> H512 {
+        ethash::calc_dataset_item(&self.cache, index)
+    }
+
+    /// Check the proof of work of a header from this context's epoch,
+    /// returning the dataset elements it accessed alongside.
+    pub fn verify_pow(&self, header: &BlockHeader) -> (bool, Vec<(u32, H512)>) {
+        assert_eq!(
+            height_to_epoch(header.number),
+            self.epoch,
+            "header is not from this context's epoch"
+        );
+        let mut accessed = Vec::new();
+        let valid = verify_pow(header, |i| {
+            let item = self.dataset_item(i);
+            accessed.push((i, item));
+            item
+        });
+        (valid, accessed)
+    }
+}
+
 impl BlockHeader {
     const NUM_FIELDS: usize = 15;
 
+    /// How many fields added by later forks an encoded header has. Legacy
+    /// headers have 15 fields, London ones add the base fee and Shanghai ones
+    /// the withdrawals root; any other count is an error.
+    fn optional_rlp_fields(serialized: &Rlp) -> Result<usize, DecoderError> {
+        match serialized.item_count()? {
+            n if n >= Self::NUM_FIELDS && n <= Self::NUM_FIELDS + 2 => Ok(n - Self::NUM_FIELDS),
+            _ => Err(DecoderError::RlpIncorrectListLen),
+        }
+    }
+
     fn stream_rlp(&self, stream: &mut RlpStream, truncated: bool) {
-        stream.begin_list(Self::NUM_FIELDS - if truncated { 2 } else { 0 });
+        stream.begin_list(
+            Self::NUM_FIELDS - if truncated { 2 } else { 0 }
+                + if self.base_fee_per_gas.is_some() { 1 } else { 0 }
+                + if self.withdrawals_root.is_some() { 1 } else { 0 },
+        );
 
         stream.append(&self.parent_hash);
         stream.append(&self.uncles_hash);
@@ -250,6 +1118,13 @@ impl BlockHeader {
             stream.append(&self.mix_hash);
             stream.append(&self.nonce);
         }
+
+        if let Some(ref base_fee_per_gas) = self.base_fee_per_gas {
+            stream.append(base_fee_per_gas);
+        }
+        if let Some(ref withdrawals_root) = self.withdrawals_root {
+            stream.append(withdrawals_root);
+        }
     }
 }
 
@@ -259,27 +1134,46 @@ impl Encodable for BlockHeader {
     }
 }
 
+/// Longest list payload a header can have with every field at its widest:
+/// seven hashes counting the withdrawals root, the author, the bloom, four
+/// `U256`s counting the base fee, two `u64`s, the extra data and the nonce.
+pub const MAX_HEADER_PAYLOAD_LEN: usize =
+    7 * 33 + 21 + 259 + 4 * 33 + 2 * 9 + (2 + EXTRA_DATA_MAX_LEN) + 9;
+
+/// Check the outer shape of an RLP encoded header before decoding it: a
+/// single list, no longer than any valid header, holding 15 fields, 16 from
+/// London on or 17 from Shanghai on.
+pub fn precheck_header_rlp(bytes: &[u8]) -> Result<(), ProgramError> {
+    check_header_rlp_shape(bytes)
+        .map_err(|e| BridgeError::from_rlp(DecodeFrom::Header, e).to_program_error())
+}
+
+pub(crate) fn check_header_rlp_shape(bytes: &[u8]) -> Result<(), DecoderError> {
+    let rlp = Rlp::new(bytes);
+    if !rlp.is_list() {
+        return Err(DecoderError::RlpExpectedToBeList);
+    }
+    // Read the claimed length alone, so an oversized claim is rejected as such
+    let info = rlp::PayloadInfo::from(bytes)?;
+    if info.value_len > MAX_HEADER_PAYLOAD_LEN {
+        return Err(DecoderError::RlpIsTooBig);
+    }
+    if info.header_len + info.value_len != bytes.len() {
+        return Err(DecoderError::RlpInconsistentLengthAndData);
+    }
+    BlockHeader::optional_rlp_fields(&rlp).map(|_| ())
+}
+
+/// The outer shape is checked first, so an oversized header is turned away
+/// before any field is read. Each field is then copied straight into place
+/// from the RLP, rather than going through the intermediate buffers `val_at`
+/// would use, and the header must encode back to the same bytes.
 impl Decodable for BlockHeader {
     fn decode(serialized: &Rlp) -> Result<Self, DecoderError> {
-        let block_header = BlockHeader {
-            parent_hash: serialized.val_at(0)?,
-            uncles_hash: serialized.val_at(1)?,
-            author: serialized.val_at(2)?,
-            state_root: serialized.val_at(3)?,
-            transactions_root: serialized.val_at(4)?,
-            receipts_root: serialized.val_at(5)?,
-            log_bloom: serialized.val_at(6)?,
-            difficulty: serialized.val_at(7)?,
-            number: serialized.val_at(8)?,
-            gas_limit: serialized.val_at(9)?,
-            gas_used: serialized.val_at(10)?,
-            timestamp: serialized.val_at(11)?,
-            extra_data: serialized.val_at(12)?,
-            mix_hash: serialized.val_at(13)?,
-            nonce: serialized.val_at(14)?,
-        };
-
-        return Ok(block_header);
+        check_header_rlp_shape(serialized.as_raw())?;
+        let header = decode_header_fields(serialized)?;
+        check_header_rlp_canonical(serialized.as_raw(), &header)?;
+        Ok(header)
     }
 }
 
@@ -291,13 +1185,19 @@ impl ExtraData {
         &mut self.bytes[0..self.len as _]
     }
     pub fn from_slice(data: &[u8]) -> Self {
-        assert!(data.len() <= EXTRA_DATA_MAX_LEN);
+        Self::from_slice_checked(data).expect("extra data longer than EXTRA_DATA_MAX_LEN")
+    }
+    /// `None` if the data is longer than `EXTRA_DATA_MAX_LEN`.
+    pub fn from_slice_checked(data: &[u8]) -> Option<Self> {
+        if data.len() > EXTRA_DATA_MAX_LEN {
+            return None;
+        }
         let mut ret = Self {
             len: data.len() as _,
             bytes: unsafe { ::std::mem::uninitialized() },
         };
         ret.as_mut().copy_from_slice(data);
-        ret
+        Some(ret)
     }
 }
 
@@ -309,8 +1209,7 @@ impl Encodable for ExtraData {
 
 impl Decodable for ExtraData {
     fn decode(serialized: &Rlp) -> Result<Self, DecoderError> {
-        let v = Vec::<u8>::decode(serialized)?;
-        Ok(Self::from_slice(&*v))
+        Self::from_slice_checked(serialized.data()?).ok_or(DecoderError::RlpIsTooBig)
     }
 }
 
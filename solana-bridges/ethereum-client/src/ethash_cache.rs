@@ -0,0 +1,174 @@
+//! Ethash verification from the cache rather than the full dataset. Even
+//! epoch 0's cache is 16 MiB, past the 10 MiB Solana permits in an account,
+//! so only the cache items a header reads are provided, each proven against a
+//! merkle root committed to for its epoch.
+
+#[cfg(feature = "ethash")]
+use std::collections::BTreeMap;
+
+#[cfg(feature = "ethash")]
+use arrayref::{array_mut_ref, array_ref};
+use ethereum_types::{H256, H512};
+#[cfg(feature = "ethash")]
+use ethereum_types::U256;
+use rlp_derive::{RlpDecodable as RlpDecodableDerive, RlpEncodable as RlpEncodableDerive};
+#[cfg(feature = "ethash")]
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::eth::*;
+
+#[cfg(feature = "ethash")]
+const HASH_BYTES: usize = ethash::HASH_BYTES;
+
+#[cfg(feature = "ethash")]
+pub fn cache_size(epoch: u64) -> usize {
+    ethash::get_cache_size(epoch as usize)
+}
+
+#[cfg(feature = "ethash")]
+fn keccak512(input: &[u8], output: &mut [u8; HASH_BYTES]) {
+    let mut keccak512 = Keccak::v512();
+    keccak512.update(input);
+    keccak512.finalize(output);
+}
+
+/// `ethash::calc_dataset_item` over a cache of `n` items read through
+/// `cache_item`, so that only the items it touches need be at hand. `None` if
+/// one of them is missing.
+#[cfg(feature = "ethash")]
+fn dataset_item_from<F>(n: usize, index: u32, mut cache_item: F) -> Option<H512>
+where
+    F: FnMut(usize) -> Option<[u8; HASH_BYTES]>,
+{
+    const WORDS: usize = HASH_BYTES / 4;
+    let fnv = |a: u32, b: u32| a.wrapping_mul(0x0100_0193) ^ b;
+    let word = |bytes: &[u8; HASH_BYTES], j: usize| u32::from_le_bytes(*array_ref!(bytes, j * 4, 4));
+
+    let mut mix = cache_item(index as usize % n)?;
+    let first = word(&mix, 0) ^ index;
+    *array_mut_ref!(mix, 0, 4) = first.to_le_bytes();
+    let seed = mix;
+    keccak512(&seed, &mut mix);
+    for j in 0..ethash::DATASET_PARENTS {
+        let parent = fnv(index ^ j as u32, word(&mix, j % WORDS)) as usize % n;
+        let item = cache_item(parent)?;
+        for k in 0..WORDS {
+            let mixed = fnv(word(&mix, k), word(&item, k));
+            *array_mut_ref!(mix, k * 4, 4) = mixed.to_le_bytes();
+        }
+    }
+    let mixed = mix;
+    keccak512(&mixed, &mut mix);
+    Some(H512(mix))
+}
+
+/// An item of the cache, with the spine proving it against the root
+/// `cache_merkle_root` commits to.
+#[derive(Debug, Eq, PartialEq, Clone, RlpEncodableDerive, RlpDecodableDerive)]
+pub struct CacheSlice {
+    pub index: u32,
+    pub item: H512,
+    pub merkle_spine: Vec<H256>,
+}
+
+fn combine_h256(l: &H256, r: &H256) -> H256 {
+    let mut data = [0u8; 64];
+    data[..32].copy_from_slice(l.as_bytes());
+    data[32..].copy_from_slice(r.as_bytes());
+    keccak256(&data)
+}
+
+/// Every level of the Keccak merkle tree over the cache items, from the
+/// leaves up. Leaves are padded with zeros to a power of two.
+#[cfg(feature = "ethash")]
+fn cache_merkle_levels(cache: &[u8]) -> Vec<Vec<H256>> {
+    let mut leaves: Vec<H256> = cache.chunks(HASH_BYTES).map(keccak256).collect();
+    leaves.resize(leaves.len().next_power_of_two(), H256::zero());
+    let mut levels = vec![leaves];
+    while levels[levels.len() - 1].len() > 1 {
+        let next = levels[levels.len() - 1]
+            .chunks(2)
+            .map(|pair| combine_h256(&pair[0], &pair[1]))
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+/// The root to commit to for light verification against `cache`.
+#[cfg(feature = "ethash")]
+pub fn cache_merkle_root(cache: &[u8]) -> H256 {
+    cache_merkle_levels(cache).pop().unwrap()[0]
+}
+
+pub fn apply_cache_merkle_proof(slice: &CacheSlice) -> H256 {
+    let mut accum = keccak256(slice.item.as_bytes());
+    for (i, sibling) in slice.merkle_spine.iter().enumerate() {
+        accum = if (slice.index >> i) % 2 == 0 {
+            combine_h256(&accum, sibling)
+        } else {
+            combine_h256(sibling, &accum)
+        };
+    }
+    accum
+}
+
+/// The cache items `verify_pow_light` needs for `header`, for relayers
+/// holding the whole cache of its epoch.
+#[cfg(feature = "ethash")]
+pub fn cache_slices_for(cache: &[u8], header: &BlockHeader) -> Vec<CacheSlice> {
+    let epoch = height_to_epoch(header.number) as usize;
+    let n = cache.len() / HASH_BYTES;
+    let mut wanted = BTreeMap::new();
+    ethash::hashimoto(seal_hash(header), header.nonce, ethash::get_full_size(epoch), |index| {
+        dataset_item_from(n, index, |i| {
+            let item = *array_ref!(cache, i * HASH_BYTES, HASH_BYTES);
+            wanted.insert(i, item);
+            Some(item)
+        })
+        .unwrap()
+    });
+
+    let levels = cache_merkle_levels(cache);
+    wanted
+        .into_iter()
+        .map(|(index, item)| CacheSlice {
+            index: index as u32,
+            item: H512(item),
+            merkle_spine: levels[..levels.len() - 1]
+                .iter()
+                .enumerate()
+                .map(|(level, hashes)| hashes[(index >> level) ^ 1])
+                .collect(),
+        })
+        .collect()
+}
+
+/// Check a header's proof of work with Hashimoto-light, from just the cache
+/// items it reads rather than the whole cache. Each must be proven against
+/// `cache_root`, the committed root of its epoch's cache.
+#[cfg(feature = "ethash")]
+pub fn verify_pow_light(header: &BlockHeader, cache_root: H256, slices: &[CacheSlice]) -> bool {
+    let epoch = height_to_epoch(header.number) as usize;
+    let n = ethash::get_cache_size(epoch) / HASH_BYTES;
+    let mut items = BTreeMap::new();
+    for slice in slices {
+        if slice.index as usize >= n || apply_cache_merkle_proof(slice) != cache_root {
+            return false;
+        }
+        items.insert(slice.index as usize, slice.item.0);
+    }
+
+    let mut complete = true;
+    let (mix_hash, result) =
+        ethash::hashimoto(seal_hash(header), header.nonce, ethash::get_full_size(epoch), |index| {
+            dataset_item_from(n, index, |i| items.get(&i).copied()).unwrap_or_else(|| {
+                complete = false;
+                H512::zero()
+            })
+        });
+
+    complete
+        && mix_hash == header.mix_hash
+        && U256::from_big_endian(result.as_bytes()) <= ethash::cross_boundary(header.difficulty)
+}
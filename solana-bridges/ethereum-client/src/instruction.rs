@@ -1,23 +1,70 @@
 use crate::{
     eth::*,
+    ethash_cache::CacheSlice,
     types::*,
     pow_proof::*,
 };
 use arrayref::array_ref;
 
 use rlp::{self, Rlp};
+use std::io::{self, Write};
 use std::mem::size_of;
 
-use ethereum_types::{H128, H256, H512, U256};
+use ethereum_types::{Bloom, H128, H160, H256, H512, H64, U256};
 
 use rlp_derive::{RlpDecodable as RlpDecodableDerive, RlpEncodable as RlpEncodableDerive};
 
 use solana_sdk::program_error::ProgramError;
 
-#[derive(Debug, Eq, PartialEq, Clone, RlpEncodableDerive, RlpDecodableDerive)]
+#[derive(Debug, Eq, PartialEq, Clone)]
 pub struct Initialize {
     pub total_difficulty: Box<U256>,
     pub header: Box<BlockHeader>,
+    /// Difficulty floor for later headers, zero for none
+    pub min_difficulty: Box<U256>,
+    /// Root of the Ethash cache of `header`'s epoch, for `NewBlockLight`,
+    /// zero for none
+    pub cache_root: Box<H256>,
+}
+
+// Trailing fields are left out of the RLP when zero, so relayers predating
+// them keep sending two item lists.
+impl rlp::Encodable for Initialize {
+    fn rlp_append(&self, stream: &mut rlp::RlpStream) {
+        let items = if !self.cache_root.is_zero() {
+            4
+        } else if !self.min_difficulty.is_zero() {
+            3
+        } else {
+            2
+        };
+        stream.begin_list(items);
+        stream.append(&*self.total_difficulty);
+        stream.append(&*self.header);
+        if items > 2 {
+            stream.append(&*self.min_difficulty);
+        }
+        if items > 3 {
+            stream.append(&*self.cache_root);
+        }
+    }
+}
+
+impl rlp::Decodable for Initialize {
+    fn decode(serialized: &Rlp) -> Result<Self, rlp::DecoderError> {
+        let items = serialized.item_count()?;
+        if items < 2 || items > 4 {
+            return Err(rlp::DecoderError::RlpIncorrectListLen);
+        }
+        let min_difficulty = if items > 2 { serialized.val_at(2)? } else { U256::zero() };
+        let cache_root = if items > 3 { serialized.val_at(3)? } else { H256::zero() };
+        Ok(Initialize {
+            total_difficulty: Box::new(serialized.val_at(0)?),
+            header: Box::new(serialized.val_at(1)?),
+            min_difficulty: Box::new(min_difficulty),
+            cache_root: Box::new(cache_root),
+        })
+    }
 }
 
 
@@ -52,6 +99,46 @@ pub struct ProveInclusion {
     pub min_difficulty: Box<U256>,
 }
 
+#[derive(Debug, Eq, PartialEq, Clone, RlpEncodableDerive, RlpDecodableDerive)]
+pub struct VerifyTxProof {
+    pub height: u64,
+    /// RLP of the transaction index
+    pub key: Vec<u8>,
+    /// RLP list of the trie nodes from the root down
+    pub proof: Vec<u8>,
+    pub expected_value: Vec<u8>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, RlpEncodableDerive, RlpDecodableDerive)]
+pub struct VerifyReceiptProof {
+    pub height: u64,
+    /// RLP of the transaction index
+    pub key: Vec<u8>,
+    /// RLP list of the trie nodes from the root down
+    pub proof: Vec<u8>,
+    pub expected_value: Vec<u8>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, RlpEncodableDerive, RlpDecodableDerive)]
+pub struct VerifyStorageProof {
+    pub height: u64,
+    pub address: Box<H160>,
+    /// RLP list of the state trie nodes from the root down
+    pub account_proof: Vec<u8>,
+    pub slot: Box<H256>,
+    /// RLP list of the storage trie nodes from the root down
+    pub storage_proof: Vec<u8>,
+    pub expected_value: Box<H256>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, RlpEncodableDerive, RlpDecodableDerive)]
+pub struct ProveBalance {
+    pub height: u64,
+    pub address: Box<H160>,
+    /// RLP list of the state trie nodes from the root down
+    pub proof: Vec<u8>,
+}
+
 #[derive(Debug, Eq, PartialEq, Clone, RlpEncodableDerive, RlpDecodableDerive)]
 pub struct Challenge {
     pub height: u64,
@@ -62,16 +149,179 @@ pub struct Challenge {
     pub element_pair: Box<ElementPair>,
 }
 
+#[derive(Debug, Eq, PartialEq, Clone, RlpEncodableDerive, RlpDecodableDerive)]
+pub struct AreSiblings {
+    pub a: BlockHeader,
+    pub b: BlockHeader,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, RlpEncodableDerive, RlpDecodableDerive)]
+pub struct NewBlockVerified {
+    pub header: BlockHeader,
+    /// Every dataset page Hashimoto reads for `header`, in access order
+    pub dag_proof: Vec<DagNodeProof>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, RlpEncodableDerive, RlpDecodableDerive)]
+pub struct NewBlockLight {
+    pub header: BlockHeader,
+    /// Every cache item Hashimoto-light reads for `header`
+    pub cache_slices: Vec<CacheSlice>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct SetCacheRoot {
+    pub epoch: u64,
+    pub root: H256,
+}
+
+/// Everything needed to check a receipt is in a stored block, bundled so
+/// other programs can check an event in a single call.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct InclusionProof {
+    pub height: u64,
+    pub receipt_index: u64,
+    pub receipt_rlp: Vec<u8>,
+    /// Receipt trie nodes from the root down
+    pub mpt_proof: Vec<Vec<u8>>,
+}
+
+impl rlp::Encodable for InclusionProof {
+    fn rlp_append(&self, stream: &mut rlp::RlpStream) {
+        stream.begin_list(4);
+        stream.append(&self.height);
+        stream.append(&self.receipt_index);
+        stream.append(&self.receipt_rlp);
+        stream.append_list::<Vec<u8>, _>(&self.mpt_proof);
+    }
+}
+
+impl rlp::Decodable for InclusionProof {
+    fn decode(serialized: &Rlp) -> Result<Self, rlp::DecoderError> {
+        if serialized.item_count()? != 4 {
+            return Err(rlp::DecoderError::RlpIncorrectListLen);
+        }
+        Ok(InclusionProof {
+            height: serialized.val_at(0)?,
+            receipt_index: serialized.val_at(1)?,
+            receipt_rlp: serialized.val_at(2)?,
+            mpt_proof: serialized.list_at(3)?,
+        })
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct CheckBloom {
+    pub height: u64,
+    /// Log address or topic
+    pub item: Vec<u8>,
+}
+
 // TODO don't reallocate for these, and instead lazily parse the instruction.
 // That will get the instruction count down while continuing to keep the stack from growing too much
 #[derive(Debug)]
 pub enum Instruction {
     Noop,
     Initialize(Box<Initialize>),
+    /// Append a header on top of the latest block. The clock sysvar may
+    /// follow the storage account, then an archive account that receives the
+    /// RLP of the header evicted to make room, if any. For a sibling of the
+    /// latest block whose parent was evicted, that account instead holds the
+    /// parent's RLP. Once the ring is full and a next account is linked, that
+    /// account must be passed instead, and the chain continues in it.
     NewBlock(Box<BlockHeader>),
     ProvidePowElement(Box<ProvidePowElement>),
     ProveInclusion(Box<ProveInclusion>),
     Challenge(Box<Challenge>),
+    /// Write the RLP of the stored header at the given height to the output account
+    GetHeader(u64),
+    /// Replace the latest block with a heavier sibling
+    ReplaceBlock(Box<BlockHeader>),
+    VerifyTxProof(Box<VerifyTxProof>),
+    VerifyReceiptProof(Box<VerifyReceiptProof>),
+    VerifyStorageProof(Box<VerifyStorageProof>),
+    /// Append a contiguous run of headers on top of the latest block, as
+    /// `NewBlock` would one by one, the clock being checked against the last.
+    /// The archive account receives the RLP of every header evicted, one
+    /// after another. Refused while headers need PoW elements, as each Ethash
+    /// header must have its own proof of work checked.
+    NewBlocks(Vec<BlockHeader>),
+    /// Write the hash of the latest block followed by its height (u64 le) to
+    /// the output account
+    GetTip,
+    /// Like `GetHeader`, but refuses headers less than `CONFIRMATION_DEPTH`
+    /// blocks deep unless the flag allowing unconfirmed headers is set
+    GetConfirmedHeader(u64, bool),
+    /// Drop all but the given number of latest blocks. The storage account
+    /// must sign.
+    Prune(u16),
+    /// Recheck the hash links of the stored blocks between the two heights,
+    /// inclusive, writing to the output account 1 followed by the height of
+    /// the first inconsistent block (u64 le), or 0 followed by 0 if there is
+    /// none
+    VerifyChain(u64, u64),
+    /// Write the ring buffer's `StorageMetadata`, packed, to the output account
+    GetMetadata,
+    /// Write the RLP of the stored header with the given hash to the output
+    /// account
+    GetHeaderByHash(Box<H256>),
+    /// Run every check `NewBlock` would, without storing the header
+    SimulateNewBlock(Box<BlockHeader>),
+    /// Write 1 to the output account if the item may be in the log bloom of
+    /// the stored header at the given height, 0 otherwise
+    CheckBloom(Box<CheckBloom>),
+    /// Settle a fork between the latest block and the candidate tip, keeping
+    /// whichever is heavier
+    Finalize,
+    /// Drop the blocks above the given height, which must still be stored and
+    /// no more than `MAX_REORG_DEPTH` below the latest, so the chain can be
+    /// extended again from there. The storage account must sign.
+    Rollback(u64),
+    /// Write up to the given number of consecutive stored headers, starting
+    /// at the given height, to the output account: how many were written
+    /// (u16 le), then each header's RLP prefixed with its length (u16 le).
+    /// Only as many as fit the output account are written.
+    GetRange(u64, u16),
+    /// Write 1 to the output account if the two headers are at the same
+    /// height on the same parent, 0 otherwise. Nothing is read from storage.
+    AreSiblings(Box<AreSiblings>),
+    /// Append a header that links to the latest block, skipping its PoW,
+    /// difficulty and every other check. Only for building test fixtures; the
+    /// storage account must sign.
+    #[cfg(feature = "test-utils")]
+    ForceBlock(Box<BlockHeader>),
+    /// Succeed if the receipt is stored at the given index of the receipt
+    /// trie of the block at the given height.
+    VerifyInclusion(Box<InclusionProof>),
+    /// Like `NewBlock`, but with the header's proof of work checked from the
+    /// dataset pages given, so no elements are to be provided afterwards. Only
+    /// for Ethash sealed chains.
+    NewBlockVerified(Box<NewBlockVerified>),
+    /// Write the account at the given address, as of the block at the given
+    /// height, to the output account: 1 if it exists or 0 if the proof shows
+    /// it doesn't, then its nonce (u64 le) and balance (32 bytes le), both
+    /// zero for a missing account.
+    ProveBalance(Box<ProveBalance>),
+    /// Write 1 to the output account, passed after the clock sysvar, if the
+    /// latest block's timestamp is no more than the given number of seconds
+    /// behind the Solana clock, 0 if the bridge has fallen further behind.
+    CheckLiveness(u64),
+    /// Write to the output account whether the block with the given hash is
+    /// the latest block or one of its ancestors: 1 if it is, 0 if it is not,
+    /// or 2 if it is older than the retained blocks and so can't be told.
+    IsAncestor(Box<H256>),
+    /// Link the empty storage account passed second as the one `NewBlock`
+    /// continues the chain in once this one is full. The storage account
+    /// must sign.
+    LinkNextAccount,
+    /// Like `NewBlockVerified`, but with the header's proof of work checked
+    /// from the cache items given, against the cache root committed to for
+    /// its epoch.
+    NewBlockLight(Box<NewBlockLight>),
+    /// Commit to the root of the given epoch's Ethash cache for
+    /// `NewBlockLight`, in place of the one committed to before. The storage
+    /// account must sign.
+    SetCacheRoot(Box<SetCacheRoot>),
 }
 
 impl Instruction {
@@ -106,6 +356,116 @@ impl Instruction {
                 buf.push(5);
                 buf.extend_from_slice(&rlp::encode(c));
             }
+            Self::GetHeader(height) => {
+                buf.push(6);
+                buf.extend_from_slice(&height.to_le_bytes());
+            }
+            Self::ReplaceBlock(ref block) => {
+                buf.push(7);
+                buf.extend_from_slice(&rlp::encode(block));
+            }
+            Self::VerifyTxProof(ref vtp) => {
+                buf.push(8);
+                buf.extend_from_slice(&rlp::encode(vtp));
+            }
+            Self::VerifyReceiptProof(ref vrp) => {
+                buf.push(9);
+                buf.extend_from_slice(&rlp::encode(vrp));
+            }
+            Self::VerifyStorageProof(ref vsp) => {
+                buf.push(10);
+                buf.extend_from_slice(&rlp::encode(vsp));
+            }
+            Self::NewBlocks(ref blocks) => {
+                buf.push(11);
+                buf.extend_from_slice(&rlp::encode_list(blocks));
+            }
+            Self::GetTip => {
+                buf.push(12);
+            }
+            Self::GetConfirmedHeader(height, allow_unconfirmed) => {
+                buf.push(13);
+                buf.extend_from_slice(&height.to_le_bytes());
+                buf.push(allow_unconfirmed as u8);
+            }
+            Self::Prune(keep_last) => {
+                buf.push(14);
+                buf.extend_from_slice(&keep_last.to_le_bytes());
+            }
+            Self::VerifyChain(from_height, to_height) => {
+                buf.push(15);
+                buf.extend_from_slice(&from_height.to_le_bytes());
+                buf.extend_from_slice(&to_height.to_le_bytes());
+            }
+            Self::GetMetadata => {
+                buf.push(16);
+            }
+            Self::GetHeaderByHash(ref hash) => {
+                buf.push(17);
+                buf.extend_from_slice(hash.as_bytes());
+            }
+            Self::SimulateNewBlock(ref block) => {
+                buf.push(18);
+                buf.extend_from_slice(&rlp::encode(block));
+            }
+            Self::CheckBloom(ref cb) => {
+                buf.push(19);
+                buf.extend_from_slice(&cb.height.to_le_bytes());
+                buf.extend_from_slice(&cb.item);
+            }
+            Self::Finalize => {
+                buf.push(20);
+            }
+            Self::Rollback(to_height) => {
+                buf.push(21);
+                buf.extend_from_slice(&to_height.to_le_bytes());
+            }
+            Self::GetRange(from_height, count) => {
+                buf.push(22);
+                buf.extend_from_slice(&from_height.to_le_bytes());
+                buf.extend_from_slice(&count.to_le_bytes());
+            }
+            Self::AreSiblings(ref siblings) => {
+                buf.push(23);
+                buf.extend_from_slice(&rlp::encode(siblings));
+            }
+            #[cfg(feature = "test-utils")]
+            Self::ForceBlock(ref block) => {
+                buf.push(24);
+                buf.extend_from_slice(&rlp::encode(block));
+            }
+            Self::VerifyInclusion(ref proof) => {
+                buf.push(25);
+                buf.extend_from_slice(&rlp::encode(proof));
+            }
+            Self::NewBlockVerified(ref block) => {
+                buf.push(26);
+                buf.extend_from_slice(&rlp::encode(block));
+            }
+            Self::ProveBalance(ref balance) => {
+                buf.push(27);
+                buf.extend_from_slice(&rlp::encode(balance));
+            }
+            Self::CheckLiveness(max_lag_secs) => {
+                buf.push(28);
+                buf.extend_from_slice(&max_lag_secs.to_le_bytes());
+            }
+            Self::IsAncestor(ref hash) => {
+                buf.push(29);
+                buf.extend_from_slice(hash.as_bytes());
+            }
+            Self::LinkNextAccount => {
+                buf.push(30);
+            }
+            Self::NewBlockLight(ref block) => {
+                buf.push(31);
+                buf.extend_from_slice(&rlp::encode(block));
+            }
+            Self::SetCacheRoot(ref set) => {
+                buf.push(32);
+                buf.extend_from_slice(&set.epoch.to_le_bytes());
+                buf.extend_from_slice(set.root.as_bytes());
+            }
         }
         return buf;
     }
@@ -118,11 +478,11 @@ impl Instruction {
             0 => Ok(Self::Noop),
             1 => rlp
                 .as_val()
-                .map_err(|e| CustomError::from_rlp(DecodeFrom::DifficultyAndHeader, e))
+                .map_err(|e| BridgeError::from_rlp(DecodeFrom::DifficultyAndHeader, e))
                 .map(Self::Initialize),
             2 => rlp
                 .as_val()
-                .map_err(|e| CustomError::from_rlp(DecodeFrom::Header, e))
+                .map_err(|e| BridgeError::from_rlp(DecodeFrom::Header, e))
                 .map(Self::NewBlock),
             3 => {
                 let height_bytes = rest.pop_many(8)?;
@@ -139,15 +499,548 @@ impl Instruction {
             },
             4 => rlp
                 .as_val()
-                .map_err(|e| CustomError::from_rlp(DecodeFrom::Inclusion, e))
+                .map_err(|e| BridgeError::from_rlp(DecodeFrom::Inclusion, e))
                 .map(Self::ProveInclusion),
             5 => rlp
                 .as_val()
-                .map_err(|e| CustomError::from_rlp(DecodeFrom::Challenge, e))
+                .map_err(|e| BridgeError::from_rlp(DecodeFrom::Challenge, e))
                 .map(Self::Challenge),
-            _ => Err(CustomError::InvalidInstructionTag),
+            6 => {
+                let height_bytes = rest.pop_many(8)?;
+                Ok(Self::GetHeader(u64::from_le_bytes(*array_ref!(height_bytes, 0, 8))))
+            }
+            7 => rlp
+                .as_val()
+                .map_err(|e| BridgeError::from_rlp(DecodeFrom::Header, e))
+                .map(Self::ReplaceBlock),
+            8 => rlp
+                .as_val()
+                .map_err(|e| BridgeError::from_rlp(DecodeFrom::TxProof, e))
+                .map(Self::VerifyTxProof),
+            9 => rlp
+                .as_val()
+                .map_err(|e| BridgeError::from_rlp(DecodeFrom::ReceiptProof, e))
+                .map(Self::VerifyReceiptProof),
+            10 => rlp
+                .as_val()
+                .map_err(|e| BridgeError::from_rlp(DecodeFrom::StorageProof, e))
+                .map(Self::VerifyStorageProof),
+            11 => rlp
+                .as_list()
+                .map_err(|e| BridgeError::from_rlp(DecodeFrom::Header, e))
+                .map(Self::NewBlocks),
+            12 => Ok(Self::GetTip),
+            13 => {
+                let height_bytes = rest.pop_many(8)?;
+                let allow_unconfirmed = rest.pop()? != 0;
+                Ok(Self::GetConfirmedHeader(
+                    u64::from_le_bytes(*array_ref!(height_bytes, 0, 8)),
+                    allow_unconfirmed,
+                ))
+            }
+            14 => {
+                let keep_last_bytes = rest.pop_many(2)?;
+                Ok(Self::Prune(u16::from_le_bytes(*array_ref!(keep_last_bytes, 0, 2))))
+            }
+            15 => {
+                let from_height_bytes = rest.pop_many(8)?;
+                let to_height_bytes = rest.pop_many(8)?;
+                Ok(Self::VerifyChain(
+                    u64::from_le_bytes(*array_ref!(from_height_bytes, 0, 8)),
+                    u64::from_le_bytes(*array_ref!(to_height_bytes, 0, 8)),
+                ))
+            }
+            16 => Ok(Self::GetMetadata),
+            17 => {
+                let hash_bytes = rest.pop_many(32)?;
+                Ok(Self::GetHeaderByHash(Box::new(H256::from_slice(hash_bytes))))
+            }
+            18 => rlp
+                .as_val()
+                .map_err(|e| BridgeError::from_rlp(DecodeFrom::Header, e))
+                .map(Self::SimulateNewBlock),
+            19 => {
+                let height_bytes = rest.pop_many(8)?;
+                Ok(Self::CheckBloom(Box::new(CheckBloom {
+                    height: u64::from_le_bytes(*array_ref!(height_bytes, 0, 8)),
+                    item: rest.peek().to_vec(),
+                })))
+            }
+            20 => Ok(Self::Finalize),
+            21 => {
+                let to_height_bytes = rest.pop_many(8)?;
+                Ok(Self::Rollback(u64::from_le_bytes(*array_ref!(to_height_bytes, 0, 8))))
+            }
+            22 => {
+                let from_height_bytes = rest.pop_many(8)?;
+                let count_bytes = rest.pop_many(2)?;
+                Ok(Self::GetRange(
+                    u64::from_le_bytes(*array_ref!(from_height_bytes, 0, 8)),
+                    u16::from_le_bytes(*array_ref!(count_bytes, 0, 2)),
+                ))
+            }
+            23 => rlp
+                .as_val()
+                .map_err(|e| BridgeError::from_rlp(DecodeFrom::Header, e))
+                .map(Self::AreSiblings),
+            #[cfg(feature = "test-utils")]
+            24 => rlp
+                .as_val()
+                .map_err(|e| BridgeError::from_rlp(DecodeFrom::Header, e))
+                .map(Self::ForceBlock),
+            25 => rlp
+                .as_val()
+                .map_err(|e| BridgeError::from_rlp(DecodeFrom::ReceiptProof, e))
+                .map(Self::VerifyInclusion),
+            26 => rlp
+                .as_val()
+                .map_err(|e| BridgeError::from_rlp(DecodeFrom::Header, e))
+                .map(Self::NewBlockVerified),
+            27 => rlp
+                .as_val()
+                .map_err(|e| BridgeError::from_rlp(DecodeFrom::StorageProof, e))
+                .map(Self::ProveBalance),
+            28 => {
+                let max_lag_bytes = rest.pop_many(8)?;
+                Ok(Self::CheckLiveness(u64::from_le_bytes(*array_ref!(max_lag_bytes, 0, 8))))
+            }
+            29 => {
+                let hash_bytes = rest.pop_many(32)?;
+                Ok(Self::IsAncestor(Box::new(H256::from_slice(hash_bytes))))
+            }
+            30 => Ok(Self::LinkNextAccount),
+            31 => rlp
+                .as_val()
+                .map_err(|e| BridgeError::from_rlp(DecodeFrom::Header, e))
+                .map(Self::NewBlockLight),
+            32 => {
+                let epoch_bytes = rest.pop_many(8)?;
+                let root_bytes = rest.pop_many(32)?;
+                Ok(Self::SetCacheRoot(Box::new(SetCacheRoot {
+                    epoch: u64::from_le_bytes(*array_ref!(epoch_bytes, 0, 8)),
+                    root: H256::from_slice(root_bytes),
+                })))
+            }
+            _ => Err(BridgeError::InvalidInstructionTag),
         }
-        .map_err(CustomError::to_program_error);
+        .map_err(BridgeError::to_program_error);
+    }
+
+    /// Borsh encoding, for off-chain tooling. The program itself only
+    /// understands `pack`.
+    pub fn to_borsh(&self) -> Vec<u8> {
+        self.try_to_vec().expect("writing to a Vec cannot fail")
+    }
+
+    pub fn try_from_borsh(input: &[u8]) -> Result<Self, ProgramError> {
+        Self::try_from_slice(input).map_err(|_| ProgramError::InvalidInstructionData)
+    }
+}
+
+// Borsh
+//
+// Variants are tagged as in `pack`. Hashes are written as their raw bytes,
+// `U256`s as 32 bytes little endian and extra data like a `Vec<u8>`. Only as
+// much of Borsh as the instructions use is implemented, here rather than
+// through the `borsh` crate, since the program itself never needs it.
+
+pub trait BorshSerialize {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+
+    fn try_to_vec(&self) -> io::Result<Vec<u8>> {
+        let mut result = Vec::new();
+        self.serialize(&mut result)?;
+        Ok(result)
+    }
+}
+
+pub trait BorshDeserialize: Sized {
+    /// Read a value off the front of `buf`, leaving the bytes after it.
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self>;
+
+    /// Read a value taking up the whole of `input`.
+    fn try_from_slice(input: &[u8]) -> io::Result<Self> {
+        let mut buf = input;
+        let result = Self::deserialize(&mut buf)?;
+        if !buf.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Not all bytes read"));
+        }
+        Ok(result)
+    }
+}
+
+fn take<'a>(buf: &mut &'a [u8], n: usize) -> io::Result<&'a [u8]> {
+    if buf.len() < n {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Unexpected length of input"));
+    }
+    let (v, rest) = buf.split_at(n);
+    *buf = rest;
+    Ok(v)
+}
+
+macro_rules! impl_borsh_int {
+    ($($t:ty),*) => {$(
+        impl BorshSerialize for $t {
+            fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+                writer.write_all(&self.to_le_bytes())
+            }
+        }
+        impl BorshDeserialize for $t {
+            fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+                Ok(<$t>::from_le_bytes(*array_ref!(take(buf, size_of::<$t>())?, 0, size_of::<$t>())))
+            }
+        }
+    )*};
+}
+
+impl_borsh_int!(u8, u16, u32, u64);
+
+impl BorshSerialize for bool {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        (*self as u8).serialize(writer)
+    }
+}
+
+impl BorshDeserialize for bool {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        match u8::deserialize(buf)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid bool representation")),
+        }
+    }
+}
+
+macro_rules! impl_borsh_hash {
+    ($($t:ty),*) => {$(
+        impl BorshSerialize for $t {
+            fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+                writer.write_all(self.as_bytes())
+            }
+        }
+        impl BorshDeserialize for $t {
+            fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+                Ok(<$t>::from_slice(take(buf, <$t>::len_bytes())?))
+            }
+        }
+    )*};
+}
+
+impl_borsh_hash!(H64, H128, H160, H256, H512, Bloom);
+
+impl BorshSerialize for U256 {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut bytes = [0u8; 32];
+        self.to_little_endian(&mut bytes);
+        writer.write_all(&bytes)
+    }
+}
+
+impl BorshDeserialize for U256 {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        Ok(U256::from_little_endian(take(buf, 32)?))
+    }
+}
+
+impl<T: BorshSerialize + ?Sized> BorshSerialize for &T {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        (**self).serialize(writer)
+    }
+}
+
+impl<T: BorshSerialize> BorshSerialize for Box<T> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        (**self).serialize(writer)
+    }
+}
+
+impl<T: BorshDeserialize> BorshDeserialize for Box<T> {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        T::deserialize(buf).map(Box::new)
+    }
+}
+
+impl<T: BorshSerialize> BorshSerialize for Option<T> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            None => 0u8.serialize(writer),
+            Some(v) => (1u8, v).serialize(writer),
+        }
+    }
+}
+
+impl<T: BorshDeserialize> BorshDeserialize for Option<T> {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        match u8::deserialize(buf)? {
+            0 => Ok(None),
+            1 => T::deserialize(buf).map(Some),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid Option representation")),
+        }
+    }
+}
+
+impl<T: BorshSerialize> BorshSerialize for [T] {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        (self.len() as u32).serialize(writer)?;
+        for v in self {
+            v.serialize(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: BorshSerialize> BorshSerialize for Vec<T> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.as_slice().serialize(writer)
+    }
+}
+
+impl<T: BorshDeserialize> BorshDeserialize for Vec<T> {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        let len = u32::deserialize(buf)?;
+        (0..len).map(|_| T::deserialize(buf)).collect()
+    }
+}
+
+impl BorshSerialize for [H512; ProvidePowElement::ETHASH_ELEMENTS_PER_INSTRUCTION as usize] {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for v in self {
+            v.serialize(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl BorshDeserialize for [H512; ProvidePowElement::ETHASH_ELEMENTS_PER_INSTRUCTION as usize] {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        let mut elements = [H512::zero(); ProvidePowElement::ETHASH_ELEMENTS_PER_INSTRUCTION as usize];
+        for e in elements.iter_mut() {
+            *e = H512::deserialize(buf)?;
+        }
+        Ok(elements)
+    }
+}
+
+macro_rules! impl_borsh_tuple {
+    ($($v:ident: $t:ident),*) => {
+        impl<$($t: BorshSerialize),*> BorshSerialize for ($($t,)*) {
+            fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+                let ($($v,)*) = self;
+                $($v.serialize(writer)?;)*
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_borsh_tuple!(a: A, b: B);
+impl_borsh_tuple!(a: A, b: B, c: C);
+
+/// Field by field, in order.
+macro_rules! impl_borsh_struct {
+    ($t:ident { $($field:ident),* $(,)? }) => {
+        impl BorshSerialize for $t {
+            fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+                $(self.$field.serialize(writer)?;)*
+                Ok(())
+            }
+        }
+        impl BorshDeserialize for $t {
+            fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+                Ok(Self {
+                    $($field: BorshDeserialize::deserialize(buf)?,)*
+                })
+            }
+        }
+    };
+}
+
+impl BorshSerialize for ExtraData {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.as_slice().serialize(writer)
+    }
+}
+
+impl BorshDeserialize for ExtraData {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        let len = u32::deserialize(buf)? as usize;
+        if len > EXTRA_DATA_MAX_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Extra data too long"));
+        }
+        Ok(ExtraData::from_slice(take(buf, len)?))
+    }
+}
+
+impl_borsh_struct!(BlockHeader {
+    parent_hash,
+    uncles_hash,
+    author,
+    state_root,
+    transactions_root,
+    receipts_root,
+    log_bloom,
+    difficulty,
+    number,
+    gas_limit,
+    gas_used,
+    timestamp,
+    extra_data,
+    mix_hash,
+    nonce,
+    base_fee_per_gas,
+    withdrawals_root,
+});
+
+impl_borsh_struct!(ElementPair { e0, e1 });
+
+impl_borsh_struct!(Initialize { total_difficulty, header, min_difficulty, cache_root });
+
+impl_borsh_struct!(ProvidePowElement { height, chunk_offset, elements });
+
+impl_borsh_struct!(ProveInclusion {
+    height,
+    block_hash,
+    key,
+    expected_value,
+    proof,
+    min_difficulty,
+});
+
+impl_borsh_struct!(VerifyTxProof { height, key, proof, expected_value });
+
+impl_borsh_struct!(VerifyReceiptProof { height, key, proof, expected_value });
+
+impl_borsh_struct!(VerifyStorageProof {
+    height,
+    address,
+    account_proof,
+    slot,
+    storage_proof,
+    expected_value,
+});
+
+impl_borsh_struct!(ProveBalance { height, address, proof });
+
+impl_borsh_struct!(Challenge {
+    height,
+    block_hash,
+    element_index,
+    merkle_spine,
+    element_pair,
+});
+
+impl_borsh_struct!(AreSiblings { a, b });
+
+impl_borsh_struct!(DagNodeProof { element_pair, merkle_spine });
+
+impl_borsh_struct!(NewBlockVerified { header, dag_proof });
+
+impl_borsh_struct!(CacheSlice { index, item, merkle_spine });
+
+impl_borsh_struct!(NewBlockLight { header, cache_slices });
+
+impl_borsh_struct!(SetCacheRoot { epoch, root });
+
+impl_borsh_struct!(InclusionProof {
+    height,
+    receipt_index,
+    receipt_rlp,
+    mpt_proof,
+});
+
+impl_borsh_struct!(CheckBloom { height, item });
+
+impl BorshSerialize for Instruction {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match *self {
+            Self::Noop => 0u8.serialize(writer),
+            Self::Initialize(ref i) => (1u8, i).serialize(writer),
+            Self::NewBlock(ref block) => (2u8, block).serialize(writer),
+            Self::ProvidePowElement(ref ppe) => (3u8, ppe).serialize(writer),
+            Self::ProveInclusion(ref pi) => (4u8, pi).serialize(writer),
+            Self::Challenge(ref c) => (5u8, c).serialize(writer),
+            Self::GetHeader(height) => (6u8, height).serialize(writer),
+            Self::ReplaceBlock(ref block) => (7u8, block).serialize(writer),
+            Self::VerifyTxProof(ref vtp) => (8u8, vtp).serialize(writer),
+            Self::VerifyReceiptProof(ref vrp) => (9u8, vrp).serialize(writer),
+            Self::VerifyStorageProof(ref vsp) => (10u8, vsp).serialize(writer),
+            Self::NewBlocks(ref blocks) => (11u8, blocks).serialize(writer),
+            Self::GetTip => 12u8.serialize(writer),
+            Self::GetConfirmedHeader(height, allow_unconfirmed) => {
+                (13u8, height, allow_unconfirmed).serialize(writer)
+            }
+            Self::Prune(keep_last) => (14u8, keep_last).serialize(writer),
+            Self::VerifyChain(from_height, to_height) => (15u8, from_height, to_height).serialize(writer),
+            Self::GetMetadata => 16u8.serialize(writer),
+            Self::GetHeaderByHash(ref hash) => (17u8, hash).serialize(writer),
+            Self::SimulateNewBlock(ref block) => (18u8, block).serialize(writer),
+            Self::CheckBloom(ref cb) => (19u8, cb).serialize(writer),
+            Self::Finalize => 20u8.serialize(writer),
+            Self::Rollback(to_height) => (21u8, to_height).serialize(writer),
+            Self::GetRange(from_height, count) => (22u8, from_height, count).serialize(writer),
+            Self::AreSiblings(ref siblings) => (23u8, siblings).serialize(writer),
+            #[cfg(feature = "test-utils")]
+            Self::ForceBlock(ref block) => (24u8, block).serialize(writer),
+            Self::VerifyInclusion(ref proof) => (25u8, proof).serialize(writer),
+            Self::NewBlockVerified(ref block) => (26u8, block).serialize(writer),
+            Self::ProveBalance(ref balance) => (27u8, balance).serialize(writer),
+            Self::CheckLiveness(max_lag_secs) => (28u8, max_lag_secs).serialize(writer),
+            Self::IsAncestor(ref hash) => (29u8, hash).serialize(writer),
+            Self::LinkNextAccount => 30u8.serialize(writer),
+            Self::NewBlockLight(ref block) => (31u8, block).serialize(writer),
+            Self::SetCacheRoot(ref set) => (32u8, set).serialize(writer),
+        }
+    }
+}
+
+impl BorshDeserialize for Instruction {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        Ok(match u8::deserialize(buf)? {
+            0 => Self::Noop,
+            1 => Self::Initialize(BorshDeserialize::deserialize(buf)?),
+            2 => Self::NewBlock(BorshDeserialize::deserialize(buf)?),
+            3 => Self::ProvidePowElement(BorshDeserialize::deserialize(buf)?),
+            4 => Self::ProveInclusion(BorshDeserialize::deserialize(buf)?),
+            5 => Self::Challenge(BorshDeserialize::deserialize(buf)?),
+            6 => Self::GetHeader(BorshDeserialize::deserialize(buf)?),
+            7 => Self::ReplaceBlock(BorshDeserialize::deserialize(buf)?),
+            8 => Self::VerifyTxProof(BorshDeserialize::deserialize(buf)?),
+            9 => Self::VerifyReceiptProof(BorshDeserialize::deserialize(buf)?),
+            10 => Self::VerifyStorageProof(BorshDeserialize::deserialize(buf)?),
+            11 => Self::NewBlocks(BorshDeserialize::deserialize(buf)?),
+            12 => Self::GetTip,
+            13 => Self::GetConfirmedHeader(
+                BorshDeserialize::deserialize(buf)?,
+                BorshDeserialize::deserialize(buf)?,
+            ),
+            14 => Self::Prune(BorshDeserialize::deserialize(buf)?),
+            15 => Self::VerifyChain(
+                BorshDeserialize::deserialize(buf)?,
+                BorshDeserialize::deserialize(buf)?,
+            ),
+            16 => Self::GetMetadata,
+            17 => Self::GetHeaderByHash(BorshDeserialize::deserialize(buf)?),
+            18 => Self::SimulateNewBlock(BorshDeserialize::deserialize(buf)?),
+            19 => Self::CheckBloom(BorshDeserialize::deserialize(buf)?),
+            20 => Self::Finalize,
+            21 => Self::Rollback(BorshDeserialize::deserialize(buf)?),
+            22 => Self::GetRange(
+                BorshDeserialize::deserialize(buf)?,
+                BorshDeserialize::deserialize(buf)?,
+            ),
+            23 => Self::AreSiblings(BorshDeserialize::deserialize(buf)?),
+            #[cfg(feature = "test-utils")]
+            24 => Self::ForceBlock(BorshDeserialize::deserialize(buf)?),
+            25 => Self::VerifyInclusion(BorshDeserialize::deserialize(buf)?),
+            26 => Self::NewBlockVerified(BorshDeserialize::deserialize(buf)?),
+            27 => Self::ProveBalance(BorshDeserialize::deserialize(buf)?),
+            28 => Self::CheckLiveness(BorshDeserialize::deserialize(buf)?),
+            29 => Self::IsAncestor(BorshDeserialize::deserialize(buf)?),
+            30 => Self::LinkNextAccount,
+            31 => Self::NewBlockLight(BorshDeserialize::deserialize(buf)?),
+            32 => Self::SetCacheRoot(BorshDeserialize::deserialize(buf)?),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid instruction tag")),
+        })
     }
 }
 
@@ -158,13 +1051,13 @@ impl<'a> Parser<'a> {
     fn pop(&mut self) -> Result<u8, ProgramError> {
         let (&v, new) = self.0
             .split_first()
-            .ok_or(CustomError::IncompleteInstruction.to_program_error())?;
+            .ok_or(BridgeError::IncompleteInstruction.to_program_error())?;
         self.0 = new;
         Ok(v)
     }
     fn pop_many(&mut self, n: usize) -> Result<&'a [u8], ProgramError> {
         if n > self.0.len() {
-            return Err(CustomError::IncompleteInstruction.to_program_error());
+            return Err(BridgeError::IncompleteInstruction.to_program_error());
         }
         let (v, new) = self.0
             .split_at(n);
@@ -1,23 +1,46 @@
-use std::mem;
+use std::{mem, ptr};
 
 use solana_program::info;
-use solana_sdk::program_error::ProgramError;
+use solana_sdk::{program_error::ProgramError, pubkey::Pubkey};
 
-use ethereum_types::U256;
+use ethereum_types::{H256, U256};
 
+use crate::clique::Signers;
 use crate::{
-    eth::BlockHeader,
+    eth::{block_hash, expected_difficulty, BlockHeader, ConsensusMode},
     pow_proof::AccessedElements,
+    types::BridgeError,
 };
 
-pub const BLOCKS_OFFSET: usize = mem::size_of::<usize>() + mem::size_of::<u64>() + 8; // TODO better
-pub const MIN_BUF_SIZE: usize = BLOCKS_OFFSET + mem::size_of::<RingItem>();
+pub const BLOCKS_OFFSET: usize = mem::size_of::<StorageT<[RingItem; 0]>>();
+pub const MIN_BUF_SIZE: usize = required_account_size(1);
+
+/// Account data length for storage retaining `capacity` headers
+pub const fn required_account_size(capacity: usize) -> usize {
+    BLOCKS_OFFSET + capacity * mem::size_of::<RingItem>()
+}
+
+/// How many blocks must be built on a header before it is considered final
+pub const CONFIRMATION_DEPTH: u64 = 12;
+
+/// Most blocks below the latest that `Rollback` may rewind to, so a
+/// compromised relayer can't rewrite deep history. `ReplaceBlock` only ever
+/// rewinds the latest block.
+pub const MAX_REORG_DEPTH: u64 = 64;
 
 pub const STORAGE_ALIGN: usize = std::mem::align_of::<StorageScrach>();
 
+// Storage is viewed in place, so integers are laid out in the target's byte
+// order: `u64`s and `U256` limbs must be little-endian, as on BPF, for
+// accounts to read the same on and off chain. Hashes are byte arrays either way.
+#[cfg(not(target_endian = "little"))]
+compile_error!("storage accounts are laid out little-endian");
+
 #[derive(Debug)]
 pub struct RingItem {
     pub total_difficulty: U256,
+    /// `block_hash` of `header`, so it needn't be recomputed
+    pub hash: H256,
     pub header: BlockHeader,
     pub elements: AccessedElements,
 }
@@ -33,6 +56,16 @@ impl ElementChunkSet {
     pub const READY_FOR_BLOCK: Self = ElementChunkSet(0);
     pub const NEED_ALL_ELEMS: Self = ElementChunkSet(!0);
 
+    /// What a freshly written block is waiting for. Only Ethash sealed
+    /// headers have elements to provide, and only in builds with the `ethash`
+    /// feature; without it their proof of work is skipped.
+    pub fn new_block(mode: ConsensusMode) -> Self {
+        match mode {
+            ConsensusMode::Ethash if cfg!(feature = "ethash") => Self::NEED_ALL_ELEMS,
+            _ => Self::READY_FOR_BLOCK,
+        }
+    }
+
     pub fn set_has_chunk(&mut self, i: u8) {
         self.0 &= !(1 << i);
     }
@@ -41,6 +74,16 @@ impl ElementChunkSet {
     }
 }
 
+/// A sibling of the latest block, kept until `Finalize` settles which of the
+/// two is on the heavier chain. Only one-block forks are tracked.
+#[derive(Debug)]
+pub struct CandidateTip {
+    pub present: bool,
+    pub total_difficulty: U256,
+    pub hash: H256,
+    pub header: BlockHeader,
+}
+
 #[derive(Debug)]
 #[repr(C)]
 pub struct StorageT<X: ?Sized> {
@@ -49,6 +92,26 @@ pub struct StorageT<X: ?Sized> {
     pub full: bool,
     pub ethash_elements: ElementChunkSet,
     pub dead: bool,
+    /// `ConsensusMode` chosen by `Initialize`, 0 before then
+    pub consensus_mode: u8,
+    /// `Network::fingerprint` of the network followed by the build that ran
+    /// `Initialize`
+    pub network_fingerprint: H256,
+    /// Headers with a lower difficulty are rejected, whatever the adjustment
+    /// formula allows. Set at `Initialize`.
+    pub min_difficulty: U256,
+    /// Account `NewBlock` continues the chain in once the ring is full, this
+    /// one then keeping its window of history. Zero if none was linked.
+    pub next_account: Pubkey,
+    /// Epoch of the Ethash cache `cache_root` is the root of
+    pub cache_epoch: u64,
+    /// Root of an Ethash cache, committed to by `Initialize` or
+    /// `SetCacheRoot` for `NewBlockLight`. Zero if none was.
+    pub cache_root: H256,
+    /// Only set for Clique chains, but reserved in every build so storage is
+    /// laid out the same in all of them
+    pub clique_signers: Signers,
+    pub candidate_tip: CandidateTip,
     pub headers: X,
 }
 
@@ -57,17 +120,30 @@ pub type Storage = StorageT<[RingItem]>;
 // Something sized that can be unsized, useful for some compile time math
 pub type StorageScrach = StorageT<[RingItem; 5]>;
 
-fn guard_sufficient_storage(account: &[u8]) -> Result<(), ProgramError> {
+fn guard_storage_layout(account: &[u8]) -> Result<(), ProgramError> {
     if MIN_BUF_SIZE > account.len() {
         info!("Account data length too small for holding state");
         return Err(ProgramError::AccountDataTooSmall);
     }
+    // The deprecated loader packs account data without padding, so on chain
+    // it is rarely aligned. The BPF VM tolerates unaligned loads; elsewhere
+    // the view would be unsound.
+    #[cfg(not(target_arch = "bpf"))]
+    {
+        if account.as_ptr() as usize % STORAGE_ALIGN != 0 {
+            info!("Account data misaligned for holding state");
+            return Err(ProgramError::InvalidAccountData);
+        }
+    }
     Ok(())
 }
 
+/// View the account data as storage. The ring gets as many slots as fit after
+/// `BLOCKS_OFFSET`, so the retained history grows with the account. Fails if
+/// the data is too short for one slot or, off chain, misaligned.
 #[inline]
 pub fn interp(raw_data: &[u8]) -> Result<&Storage, ProgramError> {
-    guard_sufficient_storage(raw_data)?;
+    guard_storage_layout(raw_data)?;
     let raw_len = raw_data.len();
     let block_len = raw_data[BLOCKS_OFFSET..].len() / mem::size_of::<RingItem>();
     let hacked_data = &raw_data[..block_len];
@@ -83,7 +159,7 @@ pub fn interp(raw_data: &[u8]) -> Result<&Storage, ProgramError> {
 
 #[inline]
 pub fn interp_mut(raw_data: &mut [u8]) -> Result<&mut Storage, ProgramError> {
-    guard_sufficient_storage(raw_data)?;
+    guard_storage_layout(raw_data)?;
     let raw_len = raw_data.len();
     let block_len = raw_data[BLOCKS_OFFSET..].len() / mem::size_of::<RingItem>();
     let hacked_data = &mut raw_data[..block_len];
@@ -97,6 +173,10 @@ pub fn interp_mut(raw_data: &mut [u8]) -> Result<&mut Storage, ProgramError> {
     Ok(res)
 }
 
+pub fn consensus_mode(data: &Storage) -> ConsensusMode {
+    ConsensusMode::from_stored(data.consensus_mode)
+}
+
 pub fn min_height(data: &Storage) -> u64 {
     let len = data.headers.len();
     match *data {
@@ -104,18 +184,155 @@ pub fn min_height(data: &Storage) -> u64 {
             full: false,
             offset,
             ..
-        } => data.height - offset as u64 + 1,
-        Storage { full: true, .. } => data.height - len as u64 + 1,
+        } => data.height + 1 - offset as u64,
+        Storage { full: true, .. } => data.height + 1 - len as u64,
+    }
+}
+
+/// Whether the block at `height` is retained. The retained heights form a
+/// window ending at the latest block, so no slot needs reading.
+pub fn contains_height(data: &Storage, height: u64) -> bool {
+    stored_count(data) > 0 && height <= data.height && height >= min_height(data)
+}
+
+pub fn is_confirmed(data: &Storage, height: u64) -> bool {
+    height <= data.height && data.height - height >= CONFIRMATION_DEPTH
+}
+
+// Slot arithmetic for a ring of `cap` slots. Each operand is reduced first,
+// so nothing overflows even if the stored offset was corrupted.
+
+/// The slot `n` slots on from `from`
+pub fn slot_after(from: usize, n: usize, cap: usize) -> usize {
+    let (from, n) = (from % cap, n % cap);
+    if from >= cap - n {
+        from - (cap - n)
+    } else {
+        from + n
+    }
+}
+
+/// The slot written after `offset`
+pub fn next_slot(offset: usize, cap: usize) -> usize {
+    slot_after(offset, 1, cap)
+}
+
+/// The slot `back` slots before `offset`: 1 for the latest block
+pub fn prev_slot(offset: usize, back: usize, cap: usize) -> usize {
+    slot_after(offset, cap - back % cap, cap)
+}
+
+/// The slot holding the oldest block: the next one to be overwritten once the
+/// ring is full, the first before then.
+pub fn oldest_slot(offset: usize, full: bool, cap: usize) -> usize {
+    if full {
+        offset % cap
+    } else {
+        0
     }
 }
 
 pub fn lowest_offset(data: &Storage) -> usize {
-    match *data {
-        Storage { full: false, .. } => 0,
-        Storage {
-            full: true, offset, ..
-        } => offset,
+    oldest_slot(data.offset, data.full, data.headers.len())
+}
+
+/// Number of blocks currently retained.
+pub fn stored_count(data: &Storage) -> usize {
+    if data.full {
+        data.headers.len()
+    } else {
+        data.offset
+    }
+}
+
+/// Ring buffer bookkeeping, as reported by `GetMetadata`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageMetadata {
+    pub height: u64,
+    pub offset: u64,
+    pub full: bool,
+    pub count: u64,
+    pub total_difficulty: U256,
+}
+
+impl StorageMetadata {
+    /// Length of the packed form: the integers as u64 le, the flag as one
+    /// byte and the total difficulty as 32 bytes le.
+    pub const LEN: usize = 8 + 8 + 1 + 8 + 32;
+
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(Self::LEN);
+        buf.extend_from_slice(&self.height.to_le_bytes());
+        buf.extend_from_slice(&self.offset.to_le_bytes());
+        buf.push(self.full as u8);
+        buf.extend_from_slice(&self.count.to_le_bytes());
+        let mut total_difficulty = [0u8; 32];
+        self.total_difficulty.to_little_endian(&mut total_difficulty);
+        buf.extend_from_slice(&total_difficulty);
+        buf
+    }
+}
+
+pub fn metadata(data: &Storage) -> Result<StorageMetadata, ProgramError> {
+    Ok(StorageMetadata {
+        height: data.height,
+        offset: data.offset as u64,
+        full: data.full,
+        count: stored_count(data) as u64,
+        total_difficulty: total_difficulty(data)?.unwrap_or_else(U256::zero),
+    })
+}
+
+/// Keep only the latest `keep_last` blocks, moved to the start of the ring,
+/// and zero every other slot. The height is untouched, so the next block still
+/// links onto the latest one.
+pub fn prune(data: &mut Storage, keep_last: usize) -> Result<(), BridgeError> {
+    let count = stored_count(data);
+    if keep_last == 0 || keep_last > count {
+        return Err(BridgeError::InvalidPrune);
+    }
+    let len = data.headers.len();
+    let first_kept = slot_after(lowest_offset(data), count - keep_last, len);
+    data.headers.rotate_left(first_kept);
+    unsafe {
+        ptr::write_bytes(data.headers[keep_last..].as_mut_ptr(), 0, len - keep_last);
     }
+    data.offset = keep_last % len;
+    data.full = keep_last == len;
+    // Its parent may be gone
+    data.candidate_tip.present = false;
+    Ok(())
+}
+
+/// Forget every block above `to_height`, so the chain can be extended again
+/// from there. The retained blocks are moved to the start of the ring and
+/// every other slot is zeroed, as with `prune`. At most `MAX_REORG_DEPTH`
+/// blocks can be dropped.
+pub fn rollback(data: &mut Storage, to_height: u64) -> Result<(), BridgeError> {
+    if !contains_height(data, to_height) {
+        return Err(BridgeError::InvalidRollback);
+    }
+    if data.height - to_height > MAX_REORG_DEPTH {
+        return Err(BridgeError::ReorgTooDeep);
+    }
+    let count = stored_count(data);
+    let dropped = (data.height - to_height) as usize;
+    if dropped == 0 {
+        return Ok(());
+    }
+    let keep = count - dropped;
+    let len = data.headers.len();
+    data.headers.rotate_left(lowest_offset(data));
+    unsafe {
+        ptr::write_bytes(data.headers[keep..].as_mut_ptr(), 0, len - keep);
+    }
+    data.offset = keep;
+    data.full = false;
+    data.height = to_height;
+    // Blocks below the latest were only built on once their PoW was checked
+    data.ethash_elements = ElementChunkSet::READY_FOR_BLOCK;
+    data.candidate_tip.present = false;
+    Ok(())
 }
 
 pub fn read_block<'a>(data: &'a Storage, idx: usize) -> Result<Option<&'a RingItem>, ProgramError> {
@@ -129,7 +346,6 @@ pub fn read_block<'a>(data: &'a Storage, idx: usize) -> Result<Option<&'a RingIt
         Storage { full: true, .. } if idx < len => (),
         _ => return Ok(None),
     };
-    assert!(data.height != 0);
     let ref header = data.headers[idx];
     Ok(Some(header))
 }
@@ -148,21 +364,87 @@ pub fn read_block_mut<'a>(
         Storage { full: true, .. } if idx < len => (),
         _ => return Ok(None),
     };
-    assert!(data.height != 0);
     let ref mut header = data.headers[idx];
     Ok(Some(header))
 }
 
 pub fn read_prev_block<'a>(data: &'a Storage) -> Result<Option<&'a RingItem>, ProgramError> {
+    read_block(data, prev_slot(data.offset, 1, data.headers.len()))
+}
+
+/// The parent of the latest block, if it is still retained.
+pub fn read_second_prev_block<'a>(data: &'a Storage) -> Result<Option<&'a RingItem>, ProgramError> {
     let len = data.headers.len();
-    read_block(data, (data.offset + (len - 1)) % len)
+    if len < 2 {
+        return Ok(None);
+    }
+    read_block(data, prev_slot(data.offset, 2, len))
 }
 
 pub fn read_prev_block_mut<'a>(
     data: &'a mut Storage,
 ) -> Result<Option<&'a mut RingItem>, ProgramError> {
     let len = data.headers.len();
-    read_block_mut(data, (data.offset + (len - 1)) % len)
+    read_block_mut(data, prev_slot(data.offset, 1, len))
+}
+
+/// Forget the latest block, so the next write takes its place. Only valid when
+/// immediately followed by writing a block of the same height.
+pub fn pop_block(data: &mut Storage) {
+    data.offset = prev_slot(data.offset, 1, data.headers.len());
+    data.height -= 1;
+}
+
+/// Cumulative difficulty of the stored chain, up to and including the latest block.
+pub fn total_difficulty(data: &Storage) -> Result<Option<U256>, ProgramError> {
+    Ok(read_prev_block(data)?.map(|item| item.total_difficulty))
+}
+
+/// The stored header with the most accumulated work and its total difficulty:
+/// the latest block, or the candidate tip while a heavier one awaits
+/// `Finalize`.
+pub fn best_header(data: &Storage) -> Result<Option<(&BlockHeader, U256)>, ProgramError> {
+    let tip = match read_prev_block(data)? {
+        Some(tip) => tip,
+        None => return Ok(None),
+    };
+    let candidate = &data.candidate_tip;
+    Ok(Some(if candidate.present && candidate.total_difficulty > tip.total_difficulty {
+        (&candidate.header, candidate.total_difficulty)
+    } else {
+        (&tip.header, tip.total_difficulty)
+    }))
+}
+
+/// What the next `NewBlock` must satisfy, for relayers to check a header
+/// locally before submitting it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NextExpectations {
+    pub number: u64,
+    /// The least difficulty the next header can have, reached once enough
+    /// time has passed since its parent
+    pub min_difficulty: U256,
+    /// The next header's timestamp must be later than this
+    pub parent_timestamp: u64,
+}
+
+/// Expectations for a header extending the latest block, if there is one.
+pub fn next_expectations(data: &Storage) -> Result<Option<NextExpectations>, ProgramError> {
+    let tip = match read_prev_block(data)? {
+        Some(tip) => tip,
+        None => return Ok(None),
+    };
+    let min_difficulty = match consensus_mode(data) {
+        ConsensusMode::Ethash => expected_difficulty(&tip.header, u64::max_value()),
+        // Signed out of turn, `clique::DIFF_NO_TURN`
+        ConsensusMode::Clique => U256::one(),
+        ConsensusMode::ProofOfStake => U256::zero(),
+    };
+    Ok(Some(NextExpectations {
+        number: tip.header.number + 1,
+        min_difficulty,
+        parent_timestamp: tip.header.timestamp,
+    }))
 }
 
 pub fn write_new_block_unvalidated(
@@ -182,12 +464,13 @@ pub fn write_new_block_unvalidated(
 
     {
         let ref mut x = data.headers[old_offset];
+        x.hash = block_hash(header);
         x.header = header.clone();
         x.total_difficulty = total_difficulty;
     }
 
     data.height = header.number;
-    data.offset = (old_offset + 1) % data.headers.len();
+    data.offset = next_slot(old_offset, data.headers.len());
     data.full |= data.offset <= old_offset;
 
     return Ok(());
@@ -1,11 +1,21 @@
 #![cfg(feature = "program")]
+#[macro_use]
+mod compute_units;
+
+pub mod clique;
+#[cfg(feature = "ethash")]
 pub mod epoch_roots;
 pub mod eth;
+pub mod ethash_cache;
 pub mod instruction;
 pub mod ledger_ring_buffer;
+pub mod mpt;
 pub mod pow_proof;
 pub mod processor;
-pub mod prove;
+#[cfg(feature = "ecrecover")]
+pub mod signature;
+#[cfg(not(target_arch = "bpf"))]
+pub mod snapshot;
 pub mod types;
 
 #[cfg(test)]
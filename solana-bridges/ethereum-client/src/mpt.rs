@@ -0,0 +1,208 @@
+//! Merkle Patricia trie proofs, shared by the transaction, receipt and state
+//! verifiers.
+
+use ethereum_types::H256;
+use rlp::{DecoderError, Rlp, RlpStream};
+use solana_sdk::program_error::ProgramError;
+
+use crate::{eth::keccak256, types::BridgeError};
+
+pub(crate) fn extract_nibbles(a: &[u8]) -> Vec<u8> {
+    a.iter().flat_map(|b| vec![b >> 4, b & 0x0F]).collect()
+}
+
+enum NodeRef<'a> {
+    Hash(H256),
+    Inline(&'a [u8]),
+}
+
+fn node_ref<'a>(rlp: Rlp<'a>) -> Result<Option<NodeRef<'a>>, DecoderError> {
+    if rlp.is_list() {
+        return Ok(Some(NodeRef::Inline(rlp.as_raw())));
+    }
+    match rlp.data()? {
+        [] => Ok(None),
+        hash if hash.len() == 32 => Ok(Some(NodeRef::Hash(H256::from_slice(hash)))),
+        _ => Err(DecoderError::Custom("invalid trie node reference")),
+    }
+}
+
+/// Walk the proof from `root` along the nibbles of `key`, returning the value
+/// stored under it, or `None` if the proof shows that `key` is absent.
+pub fn get_trie_value<'a, I>(
+    root: H256,
+    key: &[u8],
+    proof: I,
+) -> Result<Option<Vec<u8>>, DecoderError>
+where
+    I: IntoIterator<Item = &'a [u8]>,
+{
+    let path = extract_nibbles(key);
+    let mut proof = proof.into_iter();
+    let mut next = NodeRef::Hash(root);
+    let mut path_index = 0;
+
+    loop {
+        let node = match next {
+            NodeRef::Hash(hash) => {
+                let node = proof.next().ok_or(DecoderError::RlpIsTooShort)?;
+                if keccak256(node) != hash {
+                    return Err(DecoderError::Custom("trie node does not match its hash"));
+                }
+                node
+            }
+            NodeRef::Inline(node) => node,
+        };
+        let dec = Rlp::new(node);
+
+        let child = match dec.item_count()? {
+            17 => {
+                if path_index == path.len() {
+                    let value = dec.at(16)?.data()?;
+                    return Ok(if value.is_empty() { None } else { Some(value.to_vec()) });
+                }
+                let child = dec.at(path[path_index] as usize)?;
+                path_index += 1;
+                child
+            }
+            2 => {
+                let nibbles = extract_nibbles(dec.at(0)?.data()?);
+                let (is_leaf, partial) = match nibbles.first() {
+                    Some(0) => (false, &nibbles[2..]),
+                    Some(1) => (false, &nibbles[1..]),
+                    Some(2) => (true, &nibbles[2..]),
+                    Some(3) => (true, &nibbles[1..]),
+                    _ => return Err(DecoderError::Custom("invalid trie path prefix")),
+                };
+                if is_leaf {
+                    return Ok(if &path[path_index..] == partial {
+                        Some(dec.at(1)?.data()?.to_vec())
+                    } else {
+                        None
+                    });
+                }
+                if !path[path_index..].starts_with(partial) {
+                    return Ok(None);
+                }
+                path_index += partial.len();
+                dec.at(1)?
+            }
+            _ => return Err(DecoderError::Custom("invalid trie node")),
+        };
+
+        next = match node_ref(child)? {
+            Some(next) => next,
+            None => return Ok(None),
+        };
+    }
+}
+
+/// Walk `proof`, its nodes in order from the root, to the value stored under
+/// `key` in the trie with root `root`. `None` means the proof shows `key` is
+/// absent. A proof that does not hash up to `root` or is malformed is an
+/// `InvalidProof_BadMerkle` error.
+pub fn verify_mpt_proof(
+    root: H256,
+    key: &[u8],
+    proof: &[Vec<u8>],
+) -> Result<Option<Vec<u8>>, ProgramError> {
+    compute_units_span!("verify_mpt_proof");
+    get_trie_value(root, key, proof.iter().map(Vec::as_slice))
+        .map_err(|_| BridgeError::InvalidProof_BadMerkle.to_program_error())
+}
+
+/// Whether `proof` shows `expected_value` stored under `key`. An empty
+/// `expected_value` stands for absence.
+pub fn proves_value(root: H256, key: &[u8], proof: &[Vec<u8>], expected_value: &[u8]) -> bool {
+    match verify_mpt_proof(root, key, proof) {
+        Ok(Some(value)) => value == expected_value,
+        Ok(None) => expected_value.is_empty(),
+        Err(_) => false,
+    }
+}
+
+/// Root of the trie storing each of `values` under the RLP of its index, as
+/// the transaction and receipt tries of a block do.
+pub fn ordered_trie_root<I>(values: I) -> H256
+where
+    I: IntoIterator<Item = Vec<u8>>,
+{
+    let mut entries: Vec<(Vec<u8>, Vec<u8>)> = values
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| (extract_nibbles(&rlp::encode(&index)), value))
+        .collect();
+    if entries.is_empty() {
+        return keccak256(&rlp::NULL_RLP);
+    }
+    entries.sort();
+    keccak256(&encode_trie_node(&entries, 0))
+}
+
+/// Encode the node holding `entries`, sorted by key and sharing their first
+/// `depth` nibbles.
+fn encode_trie_node(entries: &[(Vec<u8>, Vec<u8>)], depth: usize) -> Vec<u8> {
+    let mut stream = RlpStream::new();
+    if let [(key, value)] = entries {
+        stream.begin_list(2);
+        stream.append(&hex_prefix(&key[depth..], true));
+        stream.append(value);
+        return stream.out();
+    }
+
+    let (first, last) = (&entries[0].0, &entries[entries.len() - 1].0);
+    let shared = first[depth..]
+        .iter()
+        .zip(&last[depth..])
+        .take_while(|(a, b)| a == b)
+        .count();
+    if shared > 0 {
+        stream.begin_list(2);
+        stream.append(&hex_prefix(&first[depth..depth + shared], false));
+        append_node_ref(&mut stream, encode_trie_node(entries, depth + shared));
+        return stream.out();
+    }
+
+    stream.begin_list(17);
+    // Keys ending here sort first, and at most one can
+    let (value, mut rest) = match entries.split_first() {
+        Some(((key, value), rest)) if key.len() == depth => (&value[..], rest),
+        _ => (&[][..], entries),
+    };
+    for nibble in 0..16 {
+        let count = rest.iter().take_while(|(key, _)| key[depth] == nibble).count();
+        let (children, after) = rest.split_at(count);
+        if children.is_empty() {
+            stream.append_empty_data();
+        } else {
+            append_node_ref(&mut stream, encode_trie_node(children, depth + 1));
+        }
+        rest = after;
+    }
+    stream.append(&value);
+    stream.out()
+}
+
+/// Nodes of 32 bytes or more are referred to by hash, smaller ones inline.
+fn append_node_ref(stream: &mut RlpStream, node: Vec<u8>) {
+    if node.len() < 32 {
+        stream.append_raw(&node, 1);
+    } else {
+        stream.append(&keccak256(&node));
+    }
+}
+
+/// Compact encoding of a path of nibbles, flagging its parity and whether it
+/// ends in a leaf.
+fn hex_prefix(nibbles: &[u8], is_leaf: bool) -> Vec<u8> {
+    let odd = nibbles.len() % 2 == 1;
+    let flag = (is_leaf as u8) << 1 | odd as u8;
+    let (first, rest) = if odd {
+        (flag << 4 | nibbles[0], &nibbles[1..])
+    } else {
+        (flag << 4, nibbles)
+    };
+    let mut encoded = vec![first];
+    encoded.extend(rest.chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+    encoded
+}
@@ -4,11 +4,14 @@ use arrayref::{
 };
 
 use ethereum_types::{H128, H512};
+#[cfg(feature = "ethash")]
+use ethereum_types::U256;
 
 use solana_sdk::hash::hash as sha256;
 
 use rlp_derive::{RlpDecodable as RlpDecodableDerive, RlpEncodable as RlpEncodableDerive};
 
+#[cfg(feature = "ethash")]
 use crate::{
     eth::*,
     epoch_roots::EPOCH_ROOTS,
@@ -97,11 +100,58 @@ pub fn apply_pow_element_merkle_proof(elems: &ElementPair, merkle_spine: &[H128]
     accum
 }
 
+#[cfg(feature = "ethash")]
 pub fn get_wanted_merkle_root(height: u64) -> H128 {
     EPOCH_ROOTS[height_to_epoch(height) as usize]
 }
 
-#[cfg(not(target_arch = "bpf"))]
+/// A page of the dataset Hashimoto reads, i.e. two consecutive elements, with
+/// the spine proving them against the epoch's DAG merkle root.
+#[derive(Debug, Eq, PartialEq, Clone, RlpEncodableDerive, RlpDecodableDerive)]
+pub struct DagNodeProof {
+    pub element_pair: ElementPair,
+    pub merkle_spine: Vec<H128>,
+}
+
+/// Check a header's proof of work from the dataset pages it accesses, given
+/// in access order, rather than trusting the elements as `verify_pow_indexes`
+/// does. Each page must be proven against the epoch's root, and the mix
+/// recomputed from them must match the header's.
+#[cfg(feature = "ethash")]
+pub fn verify_pow_with_witness(header: &BlockHeader, dag_proof: &[DagNodeProof]) -> bool {
+    use ethash::*;
+    let wanted_merkle_root = match EPOCH_ROOTS.get(height_to_epoch(header.number) as usize) {
+        Some(root) => *root,
+        None => return false,
+    };
+    let full_size = get_full_size(height_to_epoch(header.number) as usize);
+
+    let mut pages = dag_proof.iter();
+    let mut page = None;
+    let mut valid = true;
+    let (mix_hash, result) = hashimoto(seal_hash(header), header.nonce, full_size, |address| {
+        if address % 2 == 0 {
+            page = pages.next();
+            valid &= match page {
+                Some(p) => apply_pow_element_merkle_proof(&p.element_pair, &*p.merkle_spine, address)
+                    == wanted_merkle_root,
+                None => false,
+            };
+        }
+        match page {
+            Some(p) if address % 2 == 0 => p.element_pair.e0,
+            Some(p) => p.element_pair.e1,
+            None => H512::zero(),
+        }
+    });
+
+    valid
+        && pages.next().is_none()
+        && mix_hash == header.mix_hash
+        && U256::from_big_endian(result.as_fixed_bytes()) <= cross_boundary(header.difficulty)
+}
+
+#[cfg(all(feature = "ethash", not(target_arch = "bpf")))]
 pub fn verify_pow_indexes(ri: &mut RingItem) -> bool {
     let mut iter = ri.elements.0.iter_mut().flat_map(|x| x.iter_mut());
     verify_pow(&ri.header, |wanted_addr| {
@@ -113,7 +163,7 @@ pub fn verify_pow_indexes(ri: &mut RingItem) -> bool {
 }
 
 //TODO: remove once Keccak syscalls are added - currently runs into instruction limit
-#[cfg(target_arch = "bpf")]
+#[cfg(all(feature = "ethash", target_arch = "bpf"))]
 pub fn verify_pow_indexes(_ri: &mut RingItem) -> bool {
     return true;
 }
@@ -1,23 +1,29 @@
 #![cfg(feature = "program")]
 
-use ethereum_types::U256;
+use arrayref::array_ref;
+use ethereum_types::{H256, U256};
 
 use rlp::Rlp;
 
 use solana_program::info;
 use solana_sdk::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint_deprecated::ProgramResult,
     program_error::ProgramError,
     pubkey::Pubkey,
+    sysvar::{clock, Sysvar},
 };
 
+#[cfg(feature = "clique")]
+use crate::clique::*;
+#[cfg(feature = "ethash")]
+use crate::{ethash_cache::*, pow_proof::*};
 use crate::{
     eth::*,
     instruction::*,
     ledger_ring_buffer::*,
-    pow_proof::*,
-    prove::*,
+    mpt::*,
     types::*,
 };
 
@@ -36,11 +42,20 @@ pub fn process_instruction<'a>(
         return Err(ProgramError::IncorrectProgramId);
     }
 
+    if account.data_len() < MIN_BUF_SIZE {
+        info!("Account data length too small for holding state");
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+
     {
         let raw_data = account.try_borrow_data()?;
         let data = interp(&*raw_data)?;
         if data.dead {
-            return Err(CustomError::ContractIsDead.to_program_error());
+            return Err(BridgeError::ContractIsDead.to_program_error());
+        }
+        if stored_count(data) != 0 && data.network_fingerprint != NETWORK.fingerprint() {
+            info!("Storage was initialized for another network");
+            return Err(BridgeError::WrongNetwork.to_program_error());
         }
     }
 
@@ -59,6 +74,7 @@ pub fn process_instruction<'a>(
             let mut raw_data = account.try_borrow_mut_data()?;
             let ref mut data = *interp_mut(&mut *raw_data)?;
 
+            // Anything stored, even a lone genesis block, would be wiped
             match data {
                 Storage {
                     height: 0,
@@ -66,32 +82,204 @@ pub fn process_instruction<'a>(
                     full: false,
                     ..
                 } => (),
-                _ => return Err(CustomError::AlreadyInitialized.to_program_error()),
+                _ => return Err(BridgeError::AlreadyInitialized.to_program_error()),
             };
-            verify_block(&item.header, None).map_err(CustomError::to_program_error)?;
+            if item.header.number == 0 && block_hash(&item.header) != NETWORK.genesis_hash {
+                info!("Genesis header of another network");
+                return Err(BridgeError::WrongNetwork.to_program_error());
+            }
+            let mode = ConsensusMode::for_initial(&item.header);
+            verify_block(mode, &item.header, None).map_err(BridgeError::to_program_error)?;
+            #[cfg(feature = "clique")]
+            {
+                data.clique_signers = Signers::from_checkpoint(&item.header)
+                    .ok_or(BridgeError::InvalidCheckpoint.to_program_error())?;
+            }
 
+            data.consensus_mode = mode as u8;
+            data.network_fingerprint = NETWORK.fingerprint();
+            data.min_difficulty = *item.min_difficulty;
+            data.cache_epoch = height_to_epoch(item.header.number);
+            data.cache_root = *item.cache_root;
             write_new_block(data, &item.header, Some(&item.total_difficulty))?;
         }
         Instruction::NewBlock(header) => {
             let mut raw_data = account.try_borrow_mut_data()?;
             let ref mut data = *interp_mut(&mut *raw_data)?;
 
+            if header.number == 0 {
+                info!("Only Initialize may write a block at height zero");
+                return Err(BridgeError::VerifyHeaderFailed_NonConsecutiveHeight.to_program_error());
+            }
+            verify_tip_integrity(data)?;
+
+            if is_tip_sibling(data, &header)? {
+                // A fork, held aside until `Finalize`
+                let (parent, parent_hash, parent_total_difficulty) = match read_second_prev_block(data)? {
+                    Some(parent) => (parent.header.clone(), parent.hash, parent.total_difficulty),
+                    None => {
+                        let tip = read_prev_block(data)?.ok_or(BridgeError::BlockNotFound.to_program_error())?;
+                        let parent = supplied_parent(accounts_iter.clone(), &tip.header.parent_hash)?;
+                        (parent, tip.header.parent_hash, tip.total_difficulty - tip.header.difficulty)
+                    }
+                };
+                verify_stored_block(data, &header, (&parent, &parent_hash))?;
+                let total_difficulty = parent_total_difficulty + header.difficulty;
+                #[cfg(feature = "clique")]
+                verify_seal(&mut data.clique_signers.clone(), &header)?;
+
+                verify_clock_drift(accounts_iter, &header)?;
+
+                data.candidate_tip = CandidateTip {
+                    present: true,
+                    total_difficulty,
+                    hash: block_hash(&header),
+                    header: *header,
+                };
+                log_block(CANDIDATE_TIP_LOG, data.height, &data.candidate_tip.hash);
+            } else if data.candidate_tip.present && header.parent_hash == data.candidate_tip.hash {
+                // The fork grew, so switch over to it if it is now heavier
+                verify_next_height(data, &header)?;
+                verify_stored_block(data, &header, (&data.candidate_tip.header, &data.candidate_tip.hash))?;
+                let tip_total_difficulty = read_prev_block(data)?
+                    .ok_or(BridgeError::BlockNotFound.to_program_error())?
+                    .total_difficulty;
+                if data.candidate_tip.total_difficulty + header.difficulty <= tip_total_difficulty {
+                    return Err(BridgeError::InvalidReplacement_NotHeavier.to_program_error());
+                }
+                #[cfg(feature = "clique")]
+                verify_seal(&mut data.clique_signers, &header)?;
+
+                verify_clock_drift(accounts_iter, &header)?;
+
+                if data.ethash_elements != ElementChunkSet::READY_FOR_BLOCK {
+                    return Err(BridgeError::PowElementsPending.to_program_error());
+                }
+                let candidate = data.candidate_tip.header.clone();
+                data.candidate_tip.present = false;
+                pop_block(data);
+                write_new_block_unvalidated(data, &candidate, None)?;
+                // The candidate was held aside unproven, so it needs its PoW
+                // elements before anything is stacked on it
+                data.ethash_elements = ElementChunkSet::new_block(consensus_mode(data));
+                if data.ethash_elements != ElementChunkSet::READY_FOR_BLOCK {
+                    info!("Candidate tip promoted; provide its PoW elements, then resubmit the child");
+                    log_accepted_tip(data)?;
+                    return Ok(());
+                }
+                append_block(data, accounts_iter, &header)?;
+            } else {
+                verify_new_block(data, &header)?;
+                #[cfg(feature = "clique")]
+                verify_seal(&mut data.clique_signers, &header)?;
+
+                verify_clock_drift(accounts_iter, &header)?;
+
+                data.candidate_tip.present = false;
+                append_block(data, accounts_iter, &header)?;
+            }
+        }
+        Instruction::SimulateNewBlock(header) => {
+            let raw_data = account.try_borrow_data()?;
+            let data = interp(&*raw_data)?;
+
+            verify_new_block(data, &header)?;
+            #[cfg(feature = "clique")]
+            verify_seal(&mut data.clique_signers.clone(), &header)?;
+
+            verify_clock_drift(accounts_iter, &header)?;
+
+            if data.ethash_elements != ElementChunkSet::READY_FOR_BLOCK {
+                return Err(BridgeError::PowElementsPending.to_program_error());
+            }
+        }
+        Instruction::NewBlocks(headers) => {
+            let mut raw_data = account.try_borrow_mut_data()?;
+            let ref mut data = *interp_mut(&mut *raw_data)?;
+
+            let last = headers
+                .last()
+                .ok_or(BridgeError::IncompleteInstruction.to_program_error())?;
+            verify_next_height(data, &headers[0])?;
+            verify_tip_integrity(data)?;
+            if ElementChunkSet::new_block(consensus_mode(data)) != ElementChunkSet::READY_FOR_BLOCK {
+                info!("Each Ethash header needs its proof of work checked, so send them one at a time");
+                return Err(BridgeError::VerifyHeaderFailed_InvalidProofOfWork.to_program_error());
+            }
+            #[cfg(feature = "clique")]
+            let mut signers = data.clique_signers;
+            {
+                let tip =
+                    read_prev_block(data)?.ok_or(BridgeError::BlockNotFound.to_program_error())?;
+                let mut parent = (&tip.header, tip.hash);
+                for header in &headers {
+                    verify_stored_block(data, header, (parent.0, &parent.1))?;
+                    #[cfg(feature = "clique")]
+                    verify_seal(&mut signers, header)?;
+                    parent = (header, block_hash(header));
+                }
+            }
+            #[cfg(feature = "clique")]
+            {
+                data.clique_signers = signers;
+            }
+            verify_clock_drift(accounts_iter, last)?;
+
+            data.candidate_tip.present = false;
+            append_blocks(data, accounts_iter, &headers, false)?;
+        }
+        Instruction::ReplaceBlock(header) => {
+            let mut raw_data = account.try_borrow_mut_data()?;
+            let ref mut data = *interp_mut(&mut *raw_data)?;
+            verify_tip_integrity(data)?;
+
+            let tip_total_difficulty = read_prev_block(data)?
+                .ok_or(BridgeError::BlockNotFound.to_program_error())?
+                .total_difficulty;
             let parent =
-                read_prev_block(data)?.ok_or(CustomError::BlockNotFound.to_program_error())?;
-            verify_block(&header, Some(&parent.header)).map_err(CustomError::to_program_error)?;
+                read_second_prev_block(data)?.ok_or(BridgeError::BlockNotFound.to_program_error())?;
+            verify_stored_block(data, &header, (&parent.header, &parent.hash))?;
 
-            write_new_block(data, &header, None)?;
+            if parent.total_difficulty + header.difficulty <= tip_total_difficulty {
+                return Err(BridgeError::InvalidReplacement_NotHeavier.to_program_error());
+            }
+            #[cfg(feature = "clique")]
+            verify_seal(&mut data.clique_signers, &header)?;
+
+            pop_block(data);
+            write_new_block_unvalidated(data, &header, None)?;
+            data.ethash_elements = ElementChunkSet::new_block(consensus_mode(data));
+            data.candidate_tip.present = false;
+        }
+        Instruction::Finalize => {
+            let mut raw_data = account.try_borrow_mut_data()?;
+            let ref mut data = *interp_mut(&mut *raw_data)?;
+
+            if !data.candidate_tip.present {
+                return Err(BridgeError::NoCandidateTip.to_program_error());
+            }
+            data.candidate_tip.present = false;
+            let tip_total_difficulty = read_prev_block(data)?
+                .ok_or(BridgeError::BlockNotFound.to_program_error())?
+                .total_difficulty;
+            if data.candidate_tip.total_difficulty > tip_total_difficulty {
+                let candidate = data.candidate_tip.header.clone();
+                pop_block(data);
+                write_new_block_unvalidated(data, &candidate, None)?;
+                data.ethash_elements = ElementChunkSet::new_block(consensus_mode(data));
+            }
         }
+        #[cfg(feature = "ethash")]
         Instruction::ProvidePowElement(ppe) => {
             let mut raw_data = account.try_borrow_mut_data()?;
             let ref mut data = *interp_mut(&mut *raw_data)?;
             //println!("{} {:?}", ppe.chunk_offset, data.ethash_elements);
             if ppe.height != data.height {
-                return Err(CustomError::EthashElementsForWrongBlock.to_program_error())
+                return Err(BridgeError::EthashElementsForWrongBlock.to_program_error())
             }
             let mut bit_vec = data.ethash_elements;
             let block = read_prev_block_mut(data)?
-                .ok_or(CustomError::BlockNotFound.to_program_error())?;
+                .ok_or(BridgeError::BlockNotFound.to_program_error())?;
             for i in 0..ProvidePowElement::ETHASH_ELEMENTS_PER_INSTRUCTION {
                 let offset = ppe.chunk_offset * ProvidePowElement::ETHASH_ELEMENTS_PER_INSTRUCTION;
                 let new_value = ppe.elements[i as usize];
@@ -99,7 +287,7 @@ pub fn process_instruction<'a>(
                 match block.elements[offset + i].value {
                     _ if bit_vec.get_has_chunk(ppe.chunk_offset) => block.elements[offset + i].value = new_value,
                     h if h == new_value => (),
-                    _ => return Err(CustomError::EthashElementRewriting.to_program_error()),
+                    _ => return Err(BridgeError::EthashElementRewriting.to_program_error()),
                 }
             }
             bit_vec.set_has_chunk(ppe.chunk_offset);
@@ -109,7 +297,7 @@ pub fn process_instruction<'a>(
                 // We have all the elements now, verify PoW
                 let pow_valid = verify_pow_indexes(block);
                 if !pow_valid {
-                    return Err(CustomError::VerifyHeaderFailed_InvalidProofOfWork
+                    return Err(BridgeError::VerifyHeaderFailed_InvalidProofOfWork
                                .to_program_error());
                 }
             }
@@ -118,37 +306,93 @@ pub fn process_instruction<'a>(
 
         Instruction::ProveInclusion(pi) => {
             if account.is_writable {
-                return Err(CustomError::WritableHistoryDuringProofCheck.to_program_error());
+                return Err(BridgeError::WritableHistoryDuringProofCheck.to_program_error());
             }
             let raw_data = account.try_borrow_data()?;
             let data = interp(&*raw_data)?;
 
             let block = find_block(&data, pi.height)?;
-            if &hash_header(&block.header, false) != &*pi.block_hash {
-                return Err(CustomError::InvalidProof_BadBlockHash.to_program_error());
+            if &block.hash != &*pi.block_hash {
+                return Err(BridgeError::InvalidProof_BadBlockHash.to_program_error());
             }
 
             if &block.total_difficulty < &*pi.min_difficulty {
-                return Err(CustomError::InvalidProof_TooEasy.to_program_error());
+                return Err(BridgeError::InvalidProof_TooEasy.to_program_error());
             }
             let expected_root = block.header.receipts_root; // pi.block_hash
-            let rlp = Rlp::new(&*pi.proof);
-            let proof = rlp.iter().map(|rlp| rlp.data());
-            verify_trie_proof(expected_root, &*pi.key, proof, &*pi.expected_value)
-                .map_err(|_| CustomError::InvalidProof_BadMerkle.to_program_error())?;
+            let proof = unpack_proof(&*pi.proof)?;
+            if !verify_receipt_proof(expected_root, &*pi.key, &proof, &*pi.expected_value) {
+                return Err(BridgeError::InvalidProof_BadMerkle.to_program_error());
+            }
+        }
+        Instruction::VerifyTxProof(vtp) => {
+            if account.is_writable {
+                return Err(BridgeError::WritableHistoryDuringProofCheck.to_program_error());
+            }
+            let raw_data = account.try_borrow_data()?;
+            let data = interp(&*raw_data)?;
+
+            let block = find_block(&data, vtp.height)?;
+            let proof = unpack_proof(&*vtp.proof)?;
+            if !proves_value(
+                block.header.transactions_root,
+                &*vtp.key,
+                &proof,
+                &*vtp.expected_value,
+            ) {
+                return Err(BridgeError::InvalidProof_BadMerkle.to_program_error());
+            }
+        }
+        Instruction::VerifyReceiptProof(vrp) => {
+            if account.is_writable {
+                return Err(BridgeError::WritableHistoryDuringProofCheck.to_program_error());
+            }
+            let raw_data = account.try_borrow_data()?;
+            let data = interp(&*raw_data)?;
+
+            let block = find_block(&data, vrp.height)?;
+            let proof = unpack_proof(&*vrp.proof)?;
+            if !verify_receipt_proof(
+                block.header.receipts_root,
+                &*vrp.key,
+                &proof,
+                &*vrp.expected_value,
+            ) {
+                return Err(BridgeError::InvalidProof_BadMerkle.to_program_error());
+            }
+        }
+        Instruction::VerifyStorageProof(vsp) => {
+            if account.is_writable {
+                return Err(BridgeError::WritableHistoryDuringProofCheck.to_program_error());
+            }
+            let raw_data = account.try_borrow_data()?;
+            let data = interp(&*raw_data)?;
+
+            let block = find_block(&data, vsp.height)?;
+            let account_proof = unpack_proof(&*vsp.account_proof)?;
+            let storage_proof = unpack_proof(&*vsp.storage_proof)?;
+            let account_state =
+                verify_account_proof(block.header.state_root, *vsp.address, &account_proof)
+                    .map_err(BridgeError::to_program_error)?;
+            let value = verify_storage_proof(account_state.storage_root, *vsp.slot, &storage_proof)
+                .map_err(BridgeError::to_program_error)?;
+            if value != *vsp.expected_value {
+                return Err(BridgeError::InvalidProof_BadMerkle.to_program_error());
+            }
         }
+        #[cfg(feature = "ethash")]
         Instruction::Challenge(challenge) => {
             let mut raw_data = account.try_borrow_mut_data()?;
             let data = interp_mut(&mut *raw_data)?;
 
             let block = find_block(&data, challenge.height)?;
 
-            if &hash_header(&block.header, false) != &*challenge.block_hash {
-                return Err(CustomError::InvalidChallenge_BadBlockHash.to_program_error());
+            if &block.hash != &*challenge.block_hash {
+                return Err(BridgeError::InvalidChallenge_BadBlockHash.to_program_error());
             }
 
             if challenge.element_index >= 64 {
-                panic!("element pair index must be between 0 and 64")
+                return Err(BridgeError::InvalidChallenge_InvalidIndex.to_program_error());
             }
 
             let challenged_0 = &block.elements[challenge.element_index * 2];
@@ -166,7 +410,7 @@ pub fn process_instruction<'a>(
             });
 
             if challenge.element_pair == found {
-                return Err(CustomError::InvalidChallenge_SameElement.to_program_error());
+                return Err(BridgeError::InvalidChallenge_SameElement.to_program_error());
             }
 
             let wanted_merkle_root = get_wanted_merkle_root(challenge.height);
@@ -178,7 +422,7 @@ pub fn process_instruction<'a>(
             );
 
             if got_merkle_root != wanted_merkle_root {
-                return Err(CustomError::InvalidChallenge_BadMerkleRoot.to_program_error());
+                return Err(BridgeError::InvalidChallenge_BadMerkleRoot.to_program_error());
             }
 
             let dst_account = next_account_info(accounts_iter)?;
@@ -187,29 +431,455 @@ pub fn process_instruction<'a>(
 
             data.dead = true;
         }
+        Instruction::GetHeader(height) => {
+            let raw_data = account.try_borrow_data()?;
+            let data = interp(&*raw_data)?;
+
+            let block = find_block(&data, height)?;
+
+            let output_account = next_account_info(accounts_iter)?;
+            write_output(output_account, &rlp::encode(&block.header))?;
+        }
+        Instruction::GetHeaderByHash(hash) => {
+            let raw_data = account.try_borrow_data()?;
+            let data = interp(&*raw_data)?;
+
+            let block = find_block_by_hash(&data, &hash)?;
+
+            let output_account = next_account_info(accounts_iter)?;
+            write_output(output_account, &rlp::encode(&block.header))?;
+        }
+        Instruction::CheckBloom(cb) => {
+            let raw_data = account.try_borrow_data()?;
+            let data = interp(&*raw_data)?;
+
+            let block = find_block(&data, cb.height)?;
+            let may_contain = bloom_may_contain(&block.header.log_bloom, &cb.item);
+
+            let output_account = next_account_info(accounts_iter)?;
+            write_output(output_account, &[may_contain as u8])?;
+        }
+        Instruction::GetConfirmedHeader(height, allow_unconfirmed) => {
+            let raw_data = account.try_borrow_data()?;
+            let data = interp(&*raw_data)?;
+
+            if !allow_unconfirmed && !is_confirmed(data, height) {
+                return Err(BridgeError::HeaderNotConfirmed.to_program_error());
+            }
+            let block = find_block(&data, height)?;
+
+            let output_account = next_account_info(accounts_iter)?;
+            write_output(output_account, &rlp::encode(&block.header))?;
+        }
+        Instruction::Prune(keep_last) => {
+            if !account.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            let mut raw_data = account.try_borrow_mut_data()?;
+            let ref mut data = *interp_mut(&mut *raw_data)?;
+            prune(data, keep_last as usize).map_err(BridgeError::to_program_error)?;
+        }
+        Instruction::Rollback(to_height) => {
+            if !account.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            let mut raw_data = account.try_borrow_mut_data()?;
+            let ref mut data = *interp_mut(&mut *raw_data)?;
+            rollback(data, to_height).map_err(BridgeError::to_program_error)?;
+        }
+        #[cfg(feature = "test-utils")]
+        Instruction::ForceBlock(header) => {
+            if !account.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            let mut raw_data = account.try_borrow_mut_data()?;
+            let ref mut data = *interp_mut(&mut *raw_data)?;
+
+            verify_next_height(data, &header)?;
+            let tip = read_prev_block(data)?.ok_or(BridgeError::BlockNotFound.to_program_error())?;
+            if header.parent_hash != tip.hash {
+                return Err(BridgeError::VerifyHeaderFailed_InvalidParentHash.to_program_error());
+            }
+            // Forced past any PoW elements still pending, too
+            data.ethash_elements = ElementChunkSet::READY_FOR_BLOCK;
+            data.candidate_tip.present = false;
+            append_verified_block(data, accounts_iter, &header)?;
+        }
+        Instruction::VerifyChain(from_height, to_height) => {
+            let raw_data = account.try_borrow_data()?;
+            let data = interp(&*raw_data)?;
+
+            let inconsistency = first_inconsistency(&data, from_height, to_height)?;
+            let mut output = vec![inconsistency.is_some() as u8];
+            output.extend_from_slice(&inconsistency.unwrap_or(0).to_le_bytes());
+
+            let output_account = next_account_info(accounts_iter)?;
+            write_output(output_account, &output)?;
+        }
+        Instruction::GetRange(from_height, count) => {
+            let raw_data = account.try_borrow_data()?;
+            let data = interp(&*raw_data)?;
+
+            let output_account = next_account_info(accounts_iter)?;
+            let max_len = output_account.try_borrow_data()?.len();
+            write_output(output_account, &header_range(&data, from_height, count, max_len)?)?;
+        }
+        Instruction::AreSiblings(siblings) => {
+            let output_account = next_account_info(accounts_iter)?;
+            write_output(output_account, &[are_siblings(&siblings.a, &siblings.b) as u8])?;
+        }
+        #[cfg(feature = "ethash")]
+        Instruction::NewBlockVerified(block) => {
+            let mut raw_data = account.try_borrow_mut_data()?;
+            let ref mut data = *interp_mut(&mut *raw_data)?;
+
+            if consensus_mode(data) != ConsensusMode::Ethash {
+                info!("Only Ethash sealed headers have a proof of work to check");
+                return Err(BridgeError::VerifyHeaderFailed_InvalidProofOfWork.to_program_error());
+            }
+            verify_tip_integrity(data)?;
+            verify_new_block(data, &block.header)?;
+            if !verify_pow_with_witness(&block.header, &block.dag_proof) {
+                return Err(BridgeError::VerifyHeaderFailed_InvalidProofOfWork.to_program_error());
+            }
+
+            verify_clock_drift(accounts_iter, &block.header)?;
+
+            data.candidate_tip.present = false;
+            // Checked against the epoch's DAG root, so there is nothing to challenge
+            append_verified_block(data, accounts_iter, &block.header)?;
+        }
+        #[cfg(feature = "ethash")]
+        Instruction::NewBlockLight(block) => {
+            let mut raw_data = account.try_borrow_mut_data()?;
+            let ref mut data = *interp_mut(&mut *raw_data)?;
+
+            if consensus_mode(data) != ConsensusMode::Ethash {
+                info!("Only Ethash sealed headers have a proof of work to check");
+                return Err(BridgeError::VerifyHeaderFailed_InvalidProofOfWork.to_program_error());
+            }
+            verify_tip_integrity(data)?;
+            verify_new_block(data, &block.header)?;
+            if data.cache_root.is_zero() || data.cache_epoch != height_to_epoch(block.header.number) {
+                return Err(BridgeError::NoCacheRoot.to_program_error());
+            }
+            if !verify_pow_light(&block.header, data.cache_root, &block.cache_slices) {
+                return Err(BridgeError::VerifyHeaderFailed_InvalidProofOfWork.to_program_error());
+            }
+
+            verify_clock_drift(accounts_iter, &block.header)?;
+
+            data.candidate_tip.present = false;
+            // Checked against the committed cache root, so there is nothing to challenge
+            append_verified_block(data, accounts_iter, &block.header)?;
+        }
+        #[cfg(feature = "ethash")]
+        Instruction::SetCacheRoot(set) => {
+            if !account.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            let mut raw_data = account.try_borrow_mut_data()?;
+            let ref mut data = *interp_mut(&mut *raw_data)?;
+            data.cache_epoch = set.epoch;
+            data.cache_root = set.root;
+        }
+        Instruction::VerifyInclusion(proof) => {
+            if account.is_writable {
+                return Err(BridgeError::WritableHistoryDuringProofCheck.to_program_error());
+            }
+            let raw_data = account.try_borrow_data()?;
+            let data = interp(&*raw_data)?;
+
+            let block = find_block(&data, proof.height)?;
+            if !verify_receipt_proof(
+                block.header.receipts_root,
+                &rlp::encode(&proof.receipt_index),
+                &proof.mpt_proof,
+                &proof.receipt_rlp,
+            ) {
+                return Err(BridgeError::InvalidProof_BadMerkle.to_program_error());
+            }
+        }
+        Instruction::ProveBalance(balance) => {
+            if account.is_writable {
+                return Err(BridgeError::WritableHistoryDuringProofCheck.to_program_error());
+            }
+            let raw_data = account.try_borrow_data()?;
+            let data = interp(&*raw_data)?;
+
+            let block = find_block(&data, balance.height)?;
+            let proof = unpack_proof(&*balance.proof)?;
+            let found = find_account(block.header.state_root, *balance.address, &proof)
+                .map_err(BridgeError::to_program_error)?;
+            let mut output = vec![found.is_some() as u8];
+            let state = found.unwrap_or_else(AccountState::empty);
+            output.extend_from_slice(&state.nonce.to_le_bytes());
+            let mut balance = [0u8; 32];
+            state.balance.to_little_endian(&mut balance);
+            output.extend_from_slice(&balance);
+
+            let output_account = next_account_info(accounts_iter)?;
+            write_output(output_account, &output)?;
+        }
+        #[cfg(not(feature = "ethash"))]
+        Instruction::ProvidePowElement(_)
+        | Instruction::Challenge(_)
+        | Instruction::NewBlockVerified(_)
+        | Instruction::NewBlockLight(_)
+        | Instruction::SetCacheRoot(_) => {
+            return Err(BridgeError::EthashDisabled.to_program_error());
+        }
+        Instruction::CheckLiveness(max_lag_secs) => {
+            let raw_data = account.try_borrow_data()?;
+            let data = interp(&*raw_data)?;
+
+            let tip = read_prev_block(data)?.ok_or(BridgeError::BlockNotFound.to_program_error())?;
+            let clock = Clock::from_account_info(next_account_info(accounts_iter)?)?;
+            let lag = (clock.unix_timestamp.max(0) as u64).saturating_sub(tip.header.timestamp);
+
+            let output_account = next_account_info(accounts_iter)?;
+            write_output(output_account, &[(lag <= max_lag_secs) as u8])?;
+        }
+        Instruction::IsAncestor(hash) => {
+            let raw_data = account.try_borrow_data()?;
+            let data = interp(&*raw_data)?;
+
+            let output_account = next_account_info(accounts_iter)?;
+            write_output(output_account, &[ancestor_status(data, &hash)?])?;
+        }
+        Instruction::LinkNextAccount => {
+            if !account.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            let next_account = next_account_info(accounts_iter)?;
+            if next_account.owner != program_id
+                || next_account.key == account.key
+                || next_account.data_len() < required_account_size(2)
+                || stored_count(interp(&*next_account.try_borrow_data()?)?) != 0
+            {
+                return Err(BridgeError::InvalidNextAccount.to_program_error());
+            }
+
+            let mut raw_data = account.try_borrow_mut_data()?;
+            let ref mut data = *interp_mut(&mut *raw_data)?;
+            if data.next_account != Pubkey::default() {
+                return Err(BridgeError::InvalidNextAccount.to_program_error());
+            }
+            data.next_account = *next_account.key;
+        }
+        Instruction::GetTip => {
+            let raw_data = account.try_borrow_data()?;
+            let data = interp(&*raw_data)?;
+
+            let tip = read_prev_block(data)?.ok_or(BridgeError::BlockNotFound.to_program_error())?;
+            let mut output = tip.hash.to_fixed_bytes().to_vec();
+            output.extend_from_slice(&tip.header.number.to_le_bytes());
+
+            let output_account = next_account_info(accounts_iter)?;
+            write_output(output_account, &output)?;
+        }
+        Instruction::GetMetadata => {
+            let raw_data = account.try_borrow_data()?;
+            let data = interp(&*raw_data)?;
+
+            let output_account = next_account_info(accounts_iter)?;
+            write_output(output_account, &metadata(data)?.pack())?;
+        }
     })
 }
 
 pub fn find_block<'a>(data: &'a Storage, height: u64) -> Result<&'a RingItem, ProgramError> {
-    let min_h = min_height(data);
-    if min_h > height {
-        //panic!("too old {} {}", min_h, height)
-        return Err(CustomError::BlockNotFound.to_program_error());
+    if !contains_height(data, height) {
+        return Err(BridgeError::BlockNotFound.to_program_error());
     }
+    let min_h = min_height(data);
     let mut max_h = data.height;
     if data.ethash_elements != ElementChunkSet::READY_FOR_BLOCK {
         // last block doesn't have all it's elements
-        max_h -= 1;
+        max_h = max_h
+            .checked_sub(1)
+            .ok_or(BridgeError::BlockNotFound.to_program_error())?;
     }
     if max_h < height {
         //panic!("too new {} {}", max_h, height);
-        return Err(CustomError::BlockNotFound.to_program_error());
+        return Err(BridgeError::BlockNotFound.to_program_error());
     }
-    let offset = lowest_offset(data) + (height - min_h) as usize % data.headers.len();
+    let offset = slot_after(lowest_offset(data), (height - min_h) as usize, data.headers.len());
 
     // TODO: Check that we've actually run the PoW for this one
 
-    read_block(data, offset)?.ok_or(CustomError::BlockNotFound.to_program_error())
+    read_block(data, offset)?.ok_or(BridgeError::BlockNotFound.to_program_error())
+}
+
+/// Slot `slot` of the ring buffer, read straight from the account data
+/// without reinterpreting the whole account. `None` if the account is too
+/// small or has no such slot.
+fn raw_slot(raw_data: &[u8], slot: usize) -> Option<&RingItem> {
+    if raw_data.len() < MIN_BUF_SIZE {
+        return None;
+    }
+    let slot_len = std::mem::size_of::<RingItem>();
+    let start = BLOCKS_OFFSET.checked_add(slot.checked_mul(slot_len)?)?;
+    let bytes = raw_data.get(start..start.checked_add(slot_len)?)?;
+    // FIXME use proper DST stuff once it exists, as in `interp`
+    Some(unsafe { &*(bytes.as_ptr() as *const _) })
+}
+
+/// Block number stored in slot `slot`, read without a full `interp`.
+pub fn slot_number(raw_data: &[u8], slot: usize) -> Option<u64> {
+    raw_slot(raw_data, slot).map(|item| item.header.number)
+}
+
+/// State root stored in slot `slot`, read without a full `interp`.
+pub fn slot_state_root(raw_data: &[u8], slot: usize) -> Option<H256> {
+    raw_slot(raw_data, slot).map(|item| item.header.state_root)
+}
+
+/// Hash of the latest block, read straight from the account data: only the
+/// bookkeeping fields and the newest slot are touched.
+pub fn tip_hash(raw_data: &[u8]) -> Option<H256> {
+    if raw_data.len() < MIN_BUF_SIZE {
+        return None;
+    }
+    let slots = (raw_data.len() - BLOCKS_OFFSET) / std::mem::size_of::<RingItem>();
+    // FIXME use proper DST stuff once it exists, as in `interp`
+    let prefix: &StorageT<[RingItem; 0]> = unsafe { &*(raw_data.as_ptr() as *const _) };
+    if !prefix.full && prefix.offset == 0 {
+        return None;
+    }
+    raw_slot(raw_data, prev_slot(prefix.offset, 1, slots)).map(|item| item.hash)
+}
+
+/// Like `find_block`, but looking the block up by its cached hash.
+pub fn find_block_by_hash<'a>(data: &'a Storage, hash: &H256) -> Result<&'a RingItem, ProgramError> {
+    let lowest = lowest_offset(data);
+    for i in 0..stored_count(data) {
+        if let Some(item) = read_block(data, slot_after(lowest, i, data.headers.len()))? {
+            if item.hash == *hash {
+                return find_block(data, item.header.number);
+            }
+        }
+    }
+    Err(BridgeError::BlockNotFound.to_program_error())
+}
+
+/// Height of the first stored block between `from_height` and `to_height`
+/// whose cached hash is stale or which doesn't link to its parent.
+pub fn first_inconsistency(
+    data: &Storage,
+    from_height: u64,
+    to_height: u64,
+) -> Result<Option<u64>, ProgramError> {
+    let mut parent_hash = None;
+    for height in from_height..=to_height {
+        let block = find_block(data, height)?;
+        let hash = block_hash(&block.header);
+        let linked = parent_hash.map_or(true, |h| block.header.parent_hash == h);
+        if hash != block.hash || block.header.number != height || !linked {
+            return Ok(Some(height));
+        }
+        parent_hash = Some(hash);
+    }
+    Ok(None)
+}
+
+/// The `GetRange` output for `count` headers from `from_height`, cut short to
+/// fit in `max_len` bytes. The whole range must be stored.
+pub fn header_range(
+    data: &Storage,
+    from_height: u64,
+    count: u16,
+    max_len: usize,
+) -> Result<Vec<u8>, ProgramError> {
+    if count > 0 {
+        let to_height = from_height
+            .checked_add(count as u64 - 1)
+            .ok_or(BridgeError::BlockNotFound.to_program_error())?;
+        find_block(data, from_height)?;
+        find_block(data, to_height)?;
+    }
+
+    let mut output = vec![0; 2];
+    let mut written: u16 = 0;
+    for height in (from_height..).take(count as usize) {
+        let header = rlp::encode(&find_block(data, height)?.header);
+        if output.len() + 2 + header.len() > max_len {
+            break;
+        }
+        output.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        output.extend_from_slice(&header);
+        written += 1;
+    }
+    if written == 0 && count > 0 {
+        info!("Output account data length too small for result");
+        return Err(BridgeError::OutputAccountTooSmall.to_program_error());
+    }
+    output[..2].copy_from_slice(&written.to_le_bytes());
+    Ok(output)
+}
+
+/// Hashes of up to `n` ancestors of the stored block at `height`, parent
+/// first, following parent hashes for as long as the blocks are retained.
+pub fn ancestors(data: &Storage, height: u64, n: usize) -> Vec<H256> {
+    let mut hashes = Vec::new();
+    let mut child = match find_block(data, height) {
+        Ok(block) => block,
+        Err(_) => return hashes,
+    };
+    while hashes.len() < n {
+        let parent = child.header.number.checked_sub(1).and_then(|h| find_block(data, h).ok());
+        match parent {
+            Some(parent) if parent.hash == child.header.parent_hash => {
+                hashes.push(parent.hash);
+                child = parent;
+            }
+            _ => break,
+        }
+    }
+    hashes
+}
+
+pub const NOT_ANCESTOR: u8 = 0;
+pub const ANCESTOR: u8 = 1;
+pub const ANCESTOR_UNKNOWN: u8 = 2;
+
+/// Whether the block with the given hash is the tip or one of its ancestors,
+/// following parent hashes back from the tip. A hash not met on the way is only
+/// `NOT_ANCESTOR` if the walk reached genesis, `ANCESTOR_UNKNOWN` otherwise.
+pub fn ancestor_status(data: &Storage, hash: &H256) -> Result<u8, ProgramError> {
+    let mut child = read_prev_block(data)?.ok_or(BridgeError::BlockNotFound.to_program_error())?;
+    if child.hash == *hash {
+        return Ok(ANCESTOR);
+    }
+    loop {
+        if child.header.parent_hash == *hash {
+            return Ok(ANCESTOR);
+        }
+        let parent = child.header.number.checked_sub(1).and_then(|h| find_block(data, h).ok());
+        match parent {
+            Some(parent) if parent.hash == child.header.parent_hash => child = parent,
+            _ if child.header.number == 0 => return Ok(NOT_ANCESTOR),
+            _ => return Ok(ANCESTOR_UNKNOWN),
+        }
+    }
+}
+
+fn unpack_proof(packed: &[u8]) -> Result<Vec<Vec<u8>>, ProgramError> {
+    Rlp::new(packed)
+        .iter()
+        .map(|node| node.data().map(<[u8]>::to_vec))
+        .collect::<Result<_, _>>()
+        .map_err(|_| BridgeError::InvalidProof_BadMerkle.to_program_error())
+}
+
+pub fn write_output(output_account: &AccountInfo, bytes: &[u8]) -> ProgramResult {
+    let mut output = output_account.try_borrow_mut_data()?;
+    if output.len() < bytes.len() {
+        info!("Output account data length too small for result");
+        return Err(BridgeError::OutputAccountTooSmall.to_program_error());
+    }
+    output[..bytes.len()].copy_from_slice(bytes);
+    Ok(())
 }
 
 pub fn give_bounty_to_challenger(src_account: &AccountInfo, dst_account: &AccountInfo) -> ProgramResult {
@@ -218,15 +888,262 @@ pub fn give_bounty_to_challenger(src_account: &AccountInfo, dst_account: &Accoun
     Ok(())
 }
 
+/// Check `header` may be appended on top of the latest block, short of its
+/// seal and the clock.
+fn verify_new_block(data: &Storage, header: &BlockHeader) -> ProgramResult {
+    verify_next_height(data, header)?;
+    let parent = read_prev_block(data)?.ok_or(BridgeError::BlockNotFound.to_program_error())?;
+    verify_stored_block(data, header, (&parent.header, &parent.hash))
+}
+
+/// `verify_block` under the stored consensus mode, also holding `header` to
+/// the difficulty floor set at `Initialize`.
+fn verify_stored_block(data: &Storage, header: &BlockHeader, parent: (&BlockHeader, &H256)) -> ProgramResult {
+    if header.difficulty < data.min_difficulty {
+        return Err(BridgeError::VerifyHeaderFailed_BelowMinDifficulty.to_program_error());
+    }
+    verify_block(consensus_mode(data), header, Some(parent)).map_err(BridgeError::to_program_error)
+}
+
+/// Refuse to build on a latest block that was altered behind the program's
+/// back, i.e. that no longer hashes to the hash cached alongside it.
+fn verify_tip_integrity(data: &Storage) -> ProgramResult {
+    if let Some(tip) = read_prev_block(data)? {
+        if block_hash(&tip.header) != tip.hash {
+            info!("Latest block doesn't match its cached hash");
+            return Err(BridgeError::CorruptStorage.to_program_error());
+        }
+    }
+    Ok(())
+}
+
+/// Whether `header` is a new sibling of the latest block.
+fn is_tip_sibling(data: &Storage, header: &BlockHeader) -> Result<bool, ProgramError> {
+    if header.number != data.height {
+        return Ok(false);
+    }
+    match read_prev_block(data)? {
+        Some(tip) => Ok(header.parent_hash == tip.header.parent_hash && block_hash(header) != tip.hash),
+        None => Ok(false),
+    }
+}
+
+/// The parent of the latest block when it is no longer stored, read as RLP
+/// from the account after the optional clock sysvar. It must hash to the
+/// latest block's parent hash.
+fn supplied_parent<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    accounts_iter: I,
+    parent_hash: &H256,
+) -> Result<BlockHeader, ProgramError> {
+    let parent_account = accounts_iter
+        .skip_while(|account| clock::check_id(account.key))
+        .next()
+        .ok_or(BridgeError::BlockNotFound.to_program_error())?;
+    let raw_data = parent_account.try_borrow_data()?;
+    let decode_error = |e| BridgeError::from_rlp(DecodeFrom::Header, e).to_program_error();
+    let info = Rlp::new(&*raw_data).payload_info().map_err(decode_error)?;
+    let rlp_bytes = raw_data
+        .get(..info.header_len + info.value_len)
+        .ok_or_else(|| decode_error(rlp::DecoderError::RlpIsTooShort))?;
+    if hash_rlp_header(rlp_bytes) != *parent_hash {
+        return Err(BridgeError::VerifyHeaderFailed_InvalidParentHash.to_program_error());
+    }
+    rlp::decode(rlp_bytes).map_err(decode_error)
+}
+
+/// Cheaply turn away a header that isn't the next one, such as a duplicate
+/// submission, logging the height expected so relayers can resync.
+fn verify_next_height(data: &Storage, header: &BlockHeader) -> ProgramResult {
+    let next_height = match data.height.checked_add(1) {
+        Some(next_height) => next_height,
+        None => {
+            info!("No height follows the latest block");
+            return Err(BridgeError::VerifyHeaderFailed_NonConsecutiveHeight.to_program_error());
+        }
+    };
+    if header.number != next_height {
+        info!(NEED_HEIGHT_LOG);
+        info!(next_height, 0, 0, 0, 0);
+        return Err(BridgeError::VerifyHeaderFailed_NonConsecutiveHeight.to_program_error());
+    }
+    Ok(())
+}
+
+/// Passing the clock sysvar after the storage account opts into the future
+/// drift bound. Any other account is left for the caller.
+fn verify_clock_drift<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>> + Clone>(
+    accounts_iter: &mut I,
+    header: &BlockHeader,
+) -> ProgramResult {
+    match accounts_iter.clone().next() {
+        Some(clock_account) if clock::check_id(clock_account.key) => {
+            accounts_iter.next();
+            let clock = Clock::from_account_info(clock_account)?;
+            verify_timestamp_drift(header, clock.unix_timestamp)
+                .map_err(BridgeError::to_program_error)?;
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+/// The header the next block written will overwrite, if the ring is full.
+fn next_evicted(data: &Storage) -> Result<Option<BlockHeader>, ProgramError> {
+    if !data.full {
+        return Ok(None);
+    }
+    Ok(read_block(data, data.offset)?.map(|item| item.header.clone()))
+}
+
+/// Write the RLP of the headers evicted, one after another, to the archive
+/// account, if one was passed.
+fn archive_evicted<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    accounts_iter: &mut I,
+    evicted: &[u8],
+) -> ProgramResult {
+    if evicted.is_empty() {
+        return Ok(());
+    }
+    match next_account_info(accounts_iter) {
+        Ok(archive_account) => write_output(archive_account, evicted),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Whether the next block written won't fit, the ring being full and linked
+/// to the account the chain continues in.
+fn rolls_over(data: &Storage) -> bool {
+    data.full && data.next_account != Pubkey::default()
+}
+
+/// Write a verified header on top of the latest block, archiving the header
+/// it evicts. When the ring rolls over, the header goes to the next account
+/// instead, which must be passed and still be empty.
+fn append_block<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    data: &mut Storage,
+    accounts_iter: &mut I,
+    header: &BlockHeader,
+) -> ProgramResult {
+    append_blocks(data, accounts_iter, std::slice::from_ref(header), false)
+}
+
+/// Like `append_block`, for a header whose proof of work needs no PoW
+/// elements, having been checked already or skipped.
+#[cfg(any(feature = "ethash", feature = "test-utils"))]
+fn append_verified_block<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    data: &mut Storage,
+    accounts_iter: &mut I,
+    header: &BlockHeader,
+) -> ProgramResult {
+    append_blocks(data, accounts_iter, std::slice::from_ref(header), true)
+}
+
+/// `append_block` for each of a run of verified headers in turn. Those
+/// after the ring rolls over go to the next account.
+fn append_blocks<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+    data: &mut Storage,
+    accounts_iter: &mut I,
+    headers: &[BlockHeader],
+    pow_verified: bool,
+) -> ProgramResult {
+    let mut evicted = Vec::new();
+    for (i, header) in headers.iter().enumerate() {
+        if rolls_over(data) {
+            let next_account = accounts_iter
+                .find(|account| *account.key == data.next_account)
+                .ok_or(BridgeError::InvalidNextAccount.to_program_error())?;
+            let mut next_raw_data = next_account.try_borrow_mut_data()?;
+            let next = interp_mut(&mut *next_raw_data)?;
+            if stored_count(next) != 0 {
+                return Err(BridgeError::StorageRolledOver.to_program_error());
+            }
+            roll_over(data, next)?;
+            return append_blocks(next, accounts_iter, &headers[i..], pow_verified);
+        }
+        if let Some(header) = next_evicted(data)? {
+            evicted.extend_from_slice(&rlp::encode(&header));
+        }
+        write_new_block(data, header, None)?;
+        if pow_verified {
+            data.ethash_elements = ElementChunkSet::READY_FOR_BLOCK;
+        }
+        log_accepted_tip(data)?;
+    }
+    archive_evicted(accounts_iter, &evicted)
+}
+
+/// Start the empty `next` storage off with the settings and latest block of
+/// `data`, so the chain can be extended there.
+fn roll_over(data: &Storage, next: &mut Storage) -> ProgramResult {
+    let tip = read_prev_block(data)?.ok_or(BridgeError::BlockNotFound.to_program_error())?;
+    next.consensus_mode = data.consensus_mode;
+    next.network_fingerprint = data.network_fingerprint;
+    next.min_difficulty = data.min_difficulty;
+    next.cache_epoch = data.cache_epoch;
+    next.cache_root = data.cache_root;
+    #[cfg(feature = "clique")]
+    {
+        next.clique_signers = data.clique_signers;
+    }
+    write_new_block_unvalidated(next, &tip.header, Some(&tip.total_difficulty))?;
+    next.ethash_elements = data.ethash_elements;
+    Ok(())
+}
+
+/// Check a Clique header was sealed by a current signer, in or out of turn as
+/// its difficulty says and not too soon after the signer's last block, and
+/// take on the new signer list when it is a checkpoint.
+#[cfg(feature = "clique")]
+fn verify_seal(signers: &mut Signers, header: &BlockHeader) -> ProgramResult {
+    let signer = clique_sealer(header, signers.as_slice())
+        .ok_or(BridgeError::VerifyHeaderFailed_InvalidSeal.to_program_error())?;
+    if signers.signed_recently(&signer, header.number) {
+        return Err(BridgeError::VerifyHeaderFailed_RecentlySigned.to_program_error());
+    }
+    if header.number % CLIQUE_EPOCH == 0 {
+        signers
+            .update_from_checkpoint(header)
+            .ok_or(BridgeError::InvalidCheckpoint.to_program_error())?;
+    }
+    signers.record(signer, header.number);
+    Ok(())
+}
+
+/// Logged, followed by the height and the block hash as four big endian
+/// words, when `NewBlock` makes a header the latest block, so indexers can
+/// follow the chain through transaction logs.
+pub const ACCEPTED_BLOCK_LOG: &str = "Ethereum block accepted";
+/// Logged like `ACCEPTED_BLOCK_LOG` when `NewBlock` holds a header as the
+/// candidate tip.
+pub const CANDIDATE_TIP_LOG: &str = "Ethereum block held as candidate tip";
+/// Logged when a header is not the next block, followed by the height that
+/// would be accepted as the first of five words. Relayers can parse it to
+/// resume from the right header rather than bisecting.
+pub const NEED_HEIGHT_LOG: &str = "NEED_HEIGHT";
+
+fn log_block(label: &str, height: u64, hash: &H256) {
+    let word = |i: usize| u64::from_be_bytes(*array_ref!(hash.as_bytes(), 8 * i, 8));
+    info!(label);
+    info!(height, word(0), word(1), word(2), word(3));
+}
+
+fn log_accepted_tip(data: &Storage) -> ProgramResult {
+    let tip = read_prev_block(data)?.ok_or(BridgeError::BlockNotFound.to_program_error())?;
+    log_block(ACCEPTED_BLOCK_LOG, tip.header.number, &tip.hash);
+    Ok(())
+}
+
 pub fn write_new_block(
     data: &mut Storage,
     header: &BlockHeader,
     old_total_difficulty_opt: Option<&U256>,
 ) -> Result<(), ProgramError> {
     if data.ethash_elements != ElementChunkSet::READY_FOR_BLOCK {
-        panic!("expected PoW element for previous block, but we're trying to write a new block")
+        info!("expected PoW element for previous block, but we're trying to write a new block");
+        return Err(BridgeError::PowElementsPending.to_program_error());
     }
     write_new_block_unvalidated(data, header, old_total_difficulty_opt)?;
-    data.ethash_elements = ElementChunkSet::NEED_ALL_ELEMS;
+    data.ethash_elements = ElementChunkSet::new_block(consensus_mode(data));
     Ok(())
 }
+
@@ -0,0 +1,70 @@
+//! secp256k1 public key recovery, for transaction senders and Clique signers.
+
+use ethereum_types::{H160, H256, U256};
+use secp256k1::{recover, Message, RecoveryId, Signature};
+use solana_sdk::program_error::ProgramError;
+
+use crate::{
+    eth::{keccak256, Transaction},
+    types::BridgeError,
+};
+
+/// Half the secp256k1 group order. Since Homestead, signatures with a larger
+/// `s` are rejected so that each has only one valid form.
+pub const SECP256K1N_HALF: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d,
+    0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+/// Address whose key produced `signature` (r then s) over `hash`.
+pub fn ecrecover(hash: &H256, signature: &[u8; 64], recovery_id: u8) -> Option<H160> {
+    let signature = Signature::parse(signature);
+    let recovery_id = RecoveryId::parse(recovery_id).ok()?;
+    let public_key = recover(&Message::parse(hash.as_fixed_bytes()), &signature, &recovery_id).ok()?;
+    Some(H160::from_slice(&keccak256(&public_key.serialize()[1..])[12..]))
+}
+
+/// Sender of a legacy transaction. `v` must either be 27 or 28, from before
+/// EIP-155, or bind the signature to `chain_id`.
+pub fn recover_sender(tx: &Transaction, chain_id: u64) -> Result<H160, ProgramError> {
+    let invalid = || BridgeError::InvalidSignature.to_program_error();
+    if tx.v > U256::from(u64::max_value()) || tx.s > U256::from_big_endian(&SECP256K1N_HALF) {
+        return Err(invalid());
+    }
+
+    let v = tx.v.as_u64();
+    let replay_protected = chain_id.checked_mul(2).and_then(|c| c.checked_add(35));
+    let (recovery_id, chain_id) = match (v, replay_protected) {
+        (27, _) | (28, _) => (v - 27, None),
+        (_, Some(base)) => match v.checked_sub(base) {
+            Some(recovery_id) if recovery_id <= 1 => (recovery_id, Some(chain_id)),
+            _ => return Err(invalid()),
+        },
+        _ => return Err(invalid()),
+    };
+
+    let mut signature = [0u8; 64];
+    tx.r.to_big_endian(&mut signature[..32]);
+    tx.s.to_big_endian(&mut signature[32..]);
+    ecrecover(&signing_hash(tx, chain_id), &signature, recovery_id as u8).ok_or_else(invalid)
+}
+
+/// Hash of the transaction fields the sender signs, with EIP-155's chain id
+/// and empty `r` and `s` appended when replay protected.
+pub fn signing_hash(tx: &Transaction, chain_id: Option<u64>) -> H256 {
+    let mut stream = rlp::RlpStream::new_list(if chain_id.is_some() { 9 } else { 6 });
+    stream.append(&tx.nonce);
+    stream.append(&tx.gas_price);
+    stream.append(&tx.gas_limit);
+    stream.append(&tx.to);
+    stream.append(&tx.value);
+    stream.append(&tx.data.bytes);
+    if let Some(chain_id) = chain_id {
+        stream.append(&chain_id);
+        stream.append(&0u8);
+        stream.append(&0u8);
+    }
+    keccak256(&stream.out())
+}
@@ -0,0 +1,112 @@
+//! Backing up a synced storage account and restoring it elsewhere, for
+//! operators. Nothing here runs on chain.
+
+use ethereum_types::H256;
+use serde::{Deserialize, Serialize};
+use solana_sdk::program_error::ProgramError;
+
+use crate::ledger_ring_buffer::{
+    contains_height, interp, lowest_offset, min_height, read_block, required_account_size,
+    slot_after, RingItem, Storage, BLOCKS_OFFSET, MIN_BUF_SIZE,
+};
+
+/// Bumped whenever the snapshot format changes
+pub const SNAPSHOT_VERSION: u8 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    version: u8,
+    /// The layout the storage was written with, which must match on import
+    blocks_offset: u64,
+    slot_len: u64,
+    slots: u64,
+    data: Vec<u8>,
+}
+
+fn slot_count(account_len: usize) -> usize {
+    account_len.saturating_sub(BLOCKS_OFFSET) / std::mem::size_of::<RingItem>()
+}
+
+/// Snapshot the storage in a program account's data.
+pub fn export_snapshot(data: &[u8]) -> Vec<u8> {
+    let slots = slot_count(data.len());
+    let used = required_account_size(slots).min(data.len());
+    bincode::serialize(&Snapshot {
+        version: SNAPSHOT_VERSION,
+        blocks_offset: BLOCKS_OFFSET as u64,
+        slot_len: std::mem::size_of::<RingItem>() as u64,
+        slots: slots as u64,
+        data: data[..used].to_vec(),
+    })
+    .expect("serializing to a Vec cannot fail")
+}
+
+/// Restore a snapshot into `out`, which must have room for exactly as many
+/// blocks as the account it was taken from.
+pub fn import_snapshot(snapshot: &[u8], out: &mut [u8]) -> Result<(), ProgramError> {
+    let snapshot: Snapshot =
+        bincode::deserialize(snapshot).map_err(|_| ProgramError::InvalidAccountData)?;
+    if snapshot.version != SNAPSHOT_VERSION
+        || snapshot.blocks_offset != BLOCKS_OFFSET as u64
+        || snapshot.slot_len != std::mem::size_of::<RingItem>() as u64
+        || snapshot.data.len() as u64 != snapshot.blocks_offset + snapshot.slots * snapshot.slot_len
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if out.len() < MIN_BUF_SIZE || out.len() < snapshot.data.len() {
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+    if slot_count(out.len()) as u64 != snapshot.slots {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    out[..snapshot.data.len()].copy_from_slice(&snapshot.data);
+    for byte in &mut out[snapshot.data.len()..] {
+        *byte = 0;
+    }
+    interp(out).map(|_| ())
+}
+
+/// How the chain in one storage account moved on by the time of a later
+/// look at it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    /// How many blocks higher the later latest block is
+    pub advanced: u64,
+    /// Some height retained in both holds a different block in each
+    pub reorg_detected: bool,
+    /// Highest height retained in both that holds the same block in each
+    pub common_ancestor_height: Option<u64>,
+}
+
+fn stored_hash(data: &Storage, height: u64) -> Option<H256> {
+    if !contains_height(data, height) {
+        return None;
+    }
+    let slot = slot_after(lowest_offset(data), (height - min_height(data)) as usize, data.headers.len());
+    read_block(data, slot).ok().flatten().map(|item| item.hash)
+}
+
+/// Compare storage seen at one time, `a`, with the same account seen later,
+/// `b`. Only heights both still retain can be compared, so a reorg below
+/// either window goes unnoticed.
+pub fn diff_snapshots(a: &Storage, b: &Storage) -> SnapshotDiff {
+    let mut diff = SnapshotDiff {
+        advanced: b.height.saturating_sub(a.height),
+        reorg_detected: false,
+        common_ancestor_height: None,
+    };
+    let highest = a.height.min(b.height);
+    let lowest = min_height(a).max(min_height(b));
+    for height in (lowest..=highest).rev() {
+        match (stored_hash(a, height), stored_hash(b, height)) {
+            (Some(x), Some(y)) if x == y => {
+                diff.common_ancestor_height = Some(height);
+                break;
+            }
+            (Some(_), Some(_)) => diff.reorg_detected = true,
+            _ => break,
+        }
+    }
+    diff
+}
@@ -13,8 +13,24 @@ pub const HEADER_4000XX: [&[u8]; 2] = [
 
 pub const HEADER_8996776: &[u8] = &hex!("f90215a0f28520c0b577aa94d27bfd84ac15b9a1bd0c97815ca086935fbb6f6fe69681c9a01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d4934794ea674fdde714fd979de3edf0f56aa9716b898ec8a0c1277d7c2d1dddedf1b9a49f304bef09f65b531e94cbad2629a7fa88a22690eea08c65778bbc912fd96e116b869a5ffc9b671e473a9872d8edb68cd1b613200d16a0a82e3b139ea78960b0bd858667e067ab9b161a9287aebf5927afd3346fe91ab2b901000c0b52d1276a3048372233e31022d9941b94a599117b481f800079c0921954086c480b076f95da0ef0a011839293035452bb26d30f885a014028c88478283a10c1a5c0b2b131e1896d36105a248068e026366d94948a000c0b7a335c22c03dd85656d90a0e14500cf531431223812a330c007a352608d53029658174090052127d002f2dda01600b962c9421853103940c5199f4436132446f73018eb07468c06a002881a4042080348083d090be5101296720195195083110a942849ac4282718f2520223cab1a2080eb21047a415669e40165187e3109449c4368ada546022a21064781945a9ed804068001815a812984310088012000174b43f5e28f9e0bd87092aa28cbc4930838947a88398833e83983217845ddb678f94505059452d65746865726d696e652d6575312d38a0a1b6535bc565ed913565f8c471ec88ed73f8d59c61009c148913c791a4e3e168887ac6c6600610c8fb");
 
+pub const HEADER_14037611: &[u8] = &hex!("f90214a02320c9ca606618919c2a4cf5c6012cfac99399446c60a07f084334dea25f69eca01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d4934794ea674fdde714fd979de3edf0f56aa9716b898ec8a0604a0ab7fe0d434943fbf2c525c4086818b8305349d91d6f4b205aca0759a2b8a0fdfe28e250fb15f7cb360d36ebb7dafa6da4f74543ce593baa96c27891ccac83a0cb9f9e60fb971068b76a8dece4202dde6b4075ebd90e7b2cd21c7fd8e121bba1b9010082e01d13f40116b1e1a0244090289b6920c51418685a0855031b988aef1b494313054c4002584928380267bc11cec18b0b30c456ca30651d9b06c931ea78aa0c40849859c7e0432df944341b489322b0450ce12026cafa1ba590f20af8051024fb8722a43610800381a531aa92042dd02448b1549052d6f06e4005b1000e063035c0220402a09c0124daab9028836209c446240d652c927bc7e4004b849256db5ba8d08b4a2321fd1e25c4d1dc480d18465d8600a41e864001cae44f38609d1c7414a8d62b5869d5a8001180d87228d788e852119c8a03df162471a317832622153da12fc21d828710062c7103534eb119714280201341ce6889ae926e025067872b68048d94e1ed83d6326b8401caa84183b062808461e859a88c617369612d65617374322d32a03472320df4ea70d29b89afdf195c3aa2289560a453957eea5058b57b80b908bf88d6450793e6dcec1c8532ff3f048d");
+
 pub const TEST_HEADER_0: &[u8] = &hex!("f9021aa0f779e50b45bc27e4ed236840e5dbcf7afab50beaf553be56bf76da977e10cc73a01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d493479452bc44d5378309ee2abf1539bf71de1b7d7be3b5a014c996b6934d7991643669e145b8355c63aa02cbde63d390fcf4e6181d5eea45a079b7e79dc739c31662fe6f25f65bf5a5d14299c7a7aa42c3f75b9fb05474f54ca0e28dc05418692cb7baab7e7f85c1dedb8791c275b797ea3b1ffcaec5ef2aa271b9010000000000000000000000000000000000000000000000000000000000000200000000000000000000000000000000010000000000000000000000000000000000000000000000000000000408000000000000000000000000000000000000000000000000000000000000000000000001000000000000000000000010000000000000000000000000000000000000000000000000000000400000000000100000000000000000000000000080000000000000000000000000000000000000000000100002000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000002000000000000000000000000903234373439353837313930323034343383890fe68395ba8e82d0d9845dd84a079150505945206e616e6f706f6f6c2e6f7267a0a35425f443452cf94ba4b698b00fd7b3ff4fc671dea3d5cc2dcbedbc3766f45e88af7fec6031063a17");
 
 pub const TEST_BLOCK_1_TX: &[u8] = &hex!("f904eaf90213a0c89928efed5db6530c482c236da3aaeaba6435a2450a975e9b9f1f5ff6941723a01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347940000000000000000000000000000000000000001a0f0bf02aac82e0961d87a128569740012d6e2ec99a395157ba97709a9de950fe2a04e4964659ef22d9ecee734c5f7b8bcd00680b6329206da84ae388c383f905cb0a0777f1c1c378807634128348e4f0eeca6a0e7f516ea411690ca04266323f671a4b90100000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000008302004002833007cf830186a0845f7b5d9399d883010914846765746888676f312e31352e31856c696e7578a0c4bb1584988635f3c191eb599e2c05f450488df962904171a5547ead9131e3f9881450280dc437cf3cf902d0f902cd8001830186a08001b9027c3630383036303430353233343830313536313030313035373630303038306664356235303631303131653830363130303230363030303339363030306633666536303830363034303532333438303135363030663537363030303830666435623530363030343336313036303238353736303030333536306530316338303633633630356637366331343630326435373562363030303830666435623630333336306162353635623630343035313830383036303230303138323831303338323532383338313831353138313532363032303031393135303830353139303630323030313930383038333833363030303562383338313130313536303731353738303832303135313831383430313532363032303831303139303530363035383536356235303530353035303930353039303831303139303630316631363830313536303964353738303832303338303531363030313833363032303033363130313030306130333139313638313532363032303031393135303562353039323530353035303630343035313830393130333930663335623630363036303430353138303630343030313630343035323830363030643831353236303230303137663438363536633663366632633230353736663732366336343231303030303030303030303030303030303030303030303030303030303030303030303030303038313532353039303530393035366665613236343639373036363733353832323132323063346466366139393637666230336633323038653966383534623236643635626338343665323134393963646363333135303639313431653530623036623165363437333666366336333433303030363038303033338325ad31a06be9f7bacbbc298818438802d6c202df6084649643afce090e017f1cb37c3618a031fc123f349bdb40ccf39a159a31810d0cc6cff00a920a75c4d97cad8c36c938c0");
 
 pub const TEST_BLOCK_0_TX: &[u8] = &hex!("f90215f90210a0d08f55a1789e660d82802ae3970130beb9736fc1b36c2e15f4589467dbdea06ca01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347940000000000000000000000000000000000000001a0c4f67a7baaa163869ad9461c00cca706e317ca4e4ff4e22e4843ae2af0960003a056e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421a056e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421b9010000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000830200c00483301fd280845f7b762399d883010914846765746888676f312e31352e31856c696e7578a059249dd6f99033815acf89b66da4b44e5e93d588e85e4549009152f4a513601b884983ec06833e1e43c0c0");
+
+/// Ommer list of mainnet block 14764013, whose uncles_hash is UNCLES_HASH_14764013
+pub const UNCLES_14764013: &[u8] = &hex!("f90216f90213a09f9076aeb7438dc9e3927bbcff88b1980381d8a5591a5e2323759355dd9ef0a8a01dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d4934794ea674fdde714fd979de3edf0f56aa9716b898ec8a0343afe56216c786a7da762b125afbab17f7087d4d91973c8882a14839faf7fd4a01dafcd8f132425d9193c8acf6f62276135cc97e6aff9018590ce10711d66684aa0f169809ffad04f682ea4ac33d7a4287609f133b0767ad873dafdfb755657f7d2b901007f6ef7b9b1b7ff57b7dd24dbfd5ddffe1c4597947b37bbfccf65a17f3df97f9bfe3cbfffdb6ff1503419ffdaea7fc5941fbaf92738affb07ca7f7fd1ffef6f29e5d2e1edff7dabfffbaf7f0f7d29e6e046f7fe056f586ff15b74f7a0e68e2ff1ff7b175db73f96f6e7d7ff88fb3e69fbb3fe3ef8febcefecf6f7deb313ca71f2c1fcefcbcbdf7bf056ee7ddb35be27df7e8f4dad7f703d9b2ffbf87f7cbcbd6d5f8f8befffbefe3aeff5f9f0fbdbffbc7bcfdbd4e3bfab1fe7bffffe53eedd785b3ff6cfec5b6df73d93f9f81a8fd66e597432f73eefbf9b59ebe936ff7a24238efaabdfef25afa7fdffbbe5bdf75badfc72efe1f97dc57e7fe9dfff5f5bdfa7873281e8bc688acd83e147ec8401c9c3808401c5a38f84627d9ae08a75732d77657374312d35a01598b74d7f90530f02c9035719061bfec794df6f5a4183aa95ba940c521472168845fe0e67ba2cd6b18517ba6d35fc");
+pub const UNCLES_HASH_14764013: [u8; 32] = hex!("58a694212e0416353a4d3865ccf475496b55af3a3d3b002057000741af973191");
+
+/// Mainnet EIP-1559 transaction 0x938913ef1df8cd17e0893a85586ade463014559fb1bd2d536ac282f3b1bdea53
+pub const DYNAMIC_FEE_TX: &[u8] = &hex!("02f874018201bb8405f5e10085096a1d45b782520894d696a5c568160bbbf5a1356f8ac56ee81a190588871550f7dca7000080c080a07df2299b0181d6d5b817795a7d2eff5897d0d3914ff5f602e17d5b75d32ec25fa051833973e8a8c222e682d2dcea02ad7bf3ec5bc3a86bfbcdbbaa3b853e52ad08");
+pub const DYNAMIC_FEE_TX_HASH: [u8; 32] = hex!("938913ef1df8cd17e0893a85586ade463014559fb1bd2d536ac282f3b1bdea53");
+/// Mainnet EIP-2930 transaction 0x6d38fc8aee934858815ed41273cece3b676c368e9c6e39f172313a0685e1f175
+pub const ACCESS_LIST_TX: &[u8] = &hex!("01f8ee0182034c853d9f1b88158307a120940087bb802d9c0e343f00510000729031ce00bf2780b8841e1326a300000000000000000000000088e6a0c2ddd26feeb64f039a2c41296fcb3f56400000000000000000000000000000000000000000000000000000001d3b3e730000000000000000000000000000000000000000000000000596b93e53696740000000000000000000000000000000000000000000000000000000000000000001c001a0bbfd754ed51b34d0a8577f69b4c42ce6b47fee6ecf49114bb135e7e8eadbb336a0433692134eb7e7686e9aefafa9f69c601aa977c00cc85c827782f5fb1f1cff0f");
+pub const ACCESS_LIST_TX_HASH: [u8; 32] = hex!("6d38fc8aee934858815ed41273cece3b676c368e9c6e39f172313a0685e1f175");
+/// Mainnet EIP-155 transaction 0x280cde7cdefe4b188750e76c888f13bd05ce9a4d7767730feefe8a0e50ca6fc4
+pub const EIP155_TX: &[u8] = &hex!("f9015482078b8505d21dba0083022ef1947a250d5630b4cf539739df2c5dacb4c659f2488d880c46549a521b13d8b8e47ff36ab50000000000000000000000000000000000000000000066ab5a608bd00a23f2fe000000000000000000000000000000000000000000000000000000000000008000000000000000000000000048c04ed5691981c42154c6167398f95e8f38a7ff00000000000000000000000000000000000000000000000000000000632ceac70000000000000000000000000000000000000000000000000000000000000002000000000000000000000000c02aaa39b223fe8d0a0e5c4f27ead9083c756cc20000000000000000000000006c6ee5e31d828de241282b9606c8e98ea48526e225a0c9077369501641a92ef7399ff81c21639ed4fd8fc69cb793cfa1dbfab342e10aa0615facb2f1bcf3274a354cfe384a38d0cc008a11c2dd23a69111bc6930ba27a8");
+pub const EIP155_TX_SENDER: [u8; 20] = hex!("a12e1462d0ced572f396f58b6e2d03894cd7c8a4");
@@ -5,7 +5,7 @@ use rlp;
 #[repr(u32)]
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(not(test), derive(Copy))]
-pub enum CustomError {
+pub enum BridgeError {
     IncompleteInstruction,
     InvalidInstructionTag,
 
@@ -77,8 +77,90 @@ pub enum CustomError {
     EthashElementsForWrongBlock,
     EthashElementRewriting,
 
+    #[allow(non_camel_case_types)]
+    VerifyHeaderFailed_InvalidDifficulty,
+    #[allow(non_camel_case_types)]
+    InvalidReplacement_NotHeavier,
+
+    #[cfg(not(test))]
+    DecodeTxProofInstructionFailed,
+    #[cfg(test)]
+    DecodeTxProofInstructionFailed(rlp::DecoderError),
+    #[cfg(not(test))]
+    DecodeReceiptProofInstructionFailed,
+    #[cfg(test)]
+    DecodeReceiptProofInstructionFailed(rlp::DecoderError),
+    #[cfg(not(test))]
+    DecodeStorageProofInstructionFailed,
+    #[cfg(test)]
+    DecodeStorageProofInstructionFailed(rlp::DecoderError),
+
+    #[allow(non_camel_case_types)]
+    VerifyHeaderFailed_TooFarInFuture,
+    #[allow(non_camel_case_types)]
+    VerifyHeaderFailed_InvalidGasLimit,
+
+    OutputAccountTooSmall,
+    /// The latest block is still waiting for its PoW elements
+    PowElementsPending,
+
+    #[allow(non_camel_case_types)]
+    VerifyHeaderFailed_InvalidSeal,
+    /// Clique checkpoint header without a well formed signer list
+    InvalidCheckpoint,
+    /// Fewer than `CONFIRMATION_DEPTH` blocks have been built on the header
+    HeaderNotConfirmed,
+    /// Pruning must keep at least one block and no more than are stored
+    InvalidPrune,
+    /// `Finalize` without a sibling of the latest block to settle
+    NoCandidateTip,
+    /// Storage was initialized for a network this build doesn't follow, or
+    /// a genesis header of another network was given
+    WrongNetwork,
+    /// Malformed signature, or one not valid for the expected chain
+    InvalidSignature,
+    /// Rollback target is above the latest block or no longer retained
+    InvalidRollback,
+    /// Header is at a checkpointed height but doesn't have the pinned hash
+    #[allow(non_camel_case_types)]
+    VerifyHeaderFailed_CheckpointMismatch,
+    /// Proof of stake header with a nonce or ommers
+    #[allow(non_camel_case_types)]
+    VerifyHeaderFailed_InvalidProofOfStake,
+    /// Header difficulty is below the floor set at `Initialize`
+    #[allow(non_camel_case_types)]
+    VerifyHeaderFailed_BelowMinDifficulty,
+    /// The latest block no longer matches its cached hash
+    CorruptStorage,
+    /// Header breaks a rule a hard fork set for its height
+    #[allow(non_camel_case_types)]
+    VerifyHeaderFailed_HardforkRule,
+    /// Rollback target is more than `MAX_REORG_DEPTH` blocks below the latest
+    ReorgTooDeep,
+    /// Proof of work instruction in a build without the `ethash` feature
+    EthashDisabled,
+    /// Header claims more gas used than its gas limit
+    #[allow(non_camel_case_types)]
+    VerifyHeaderFailed_GasUsedOverLimit,
+    /// Next account already linked, or not an empty storage account of this
+    /// program with room for two blocks, or not passed when it is needed
+    InvalidNextAccount,
+    /// Storage is full and the chain continues in its next account
+    StorageRolledOver,
+    /// No cache root is committed to for the header's epoch
+    NoCacheRoot,
+    /// Clique header sealed by a signer of one of the latest blocks
+    #[allow(non_camel_case_types)]
+    VerifyHeaderFailed_RecentlySigned,
 }
 
+// Clients match on the codes, so variants are only ever appended. Inserting
+// one earlier would shift these and fail the build.
+#[cfg(not(test))]
+const _: [(); 0] = [(); (BridgeError::InvalidAccountOwner as u32 != 15) as usize];
+#[cfg(not(test))]
+const _: [(); 0] = [(); (BridgeError::VerifyHeaderFailed_InvalidGasLimit as u32 != 36) as usize];
+
 pub enum DecodeFrom {
     Block,
     Header,
@@ -86,12 +168,15 @@ pub enum DecodeFrom {
     Inclusion,
     Challenge,
     PowElement,
+    TxProof,
+    ReceiptProof,
+    StorageProof,
 }
 
-impl CustomError {
+impl BridgeError {
     #[cfg(not(test))]
     pub fn from_rlp(t: DecodeFrom, _: rlp::DecoderError) -> Self {
-        use CustomError::*;
+        use BridgeError::*;
         use DecodeFrom::*;
         match t {
             Block => DecodeBlockFailed,
@@ -100,11 +185,14 @@ impl CustomError {
             PowElement => DecodePowElementFailed,
             Inclusion => DecodeInclusionInstructionFailed,
             Challenge => DecodeChallengeInstructionFailed,
+            TxProof => DecodeTxProofInstructionFailed,
+            ReceiptProof => DecodeReceiptProofInstructionFailed,
+            StorageProof => DecodeStorageProofInstructionFailed,
         }
     }
     #[cfg(test)]
     pub fn from_rlp(t: DecodeFrom, e: rlp::DecoderError) -> Self {
-        use CustomError::*;
+        use BridgeError::*;
         use DecodeFrom::*;
         match t {
             Block => DecodeBlockFailed(e),
@@ -113,6 +201,9 @@ impl CustomError {
             PowElement => DecodePowElementFailed(e),
             Inclusion => DecodeInclusionInstructionFailed(e),
             Challenge => DecodeChallengeInstructionFailed(e),
+            TxProof => DecodeTxProofInstructionFailed(e),
+            ReceiptProof => DecodeReceiptProofInstructionFailed(e),
+            StorageProof => DecodeStorageProofInstructionFailed(e),
         }
     }
 
@@ -122,7 +213,7 @@ impl CustomError {
     }
     #[cfg(test)]
     pub fn to_program_error(self) -> ProgramError {
-        use CustomError::*;
+        use BridgeError::*;
         ProgramError::Custom(match self {
             IncompleteInstruction => 0,
             InvalidInstructionTag => 1,
@@ -142,24 +233,60 @@ impl CustomError {
 
             BlockNotFound => 13,
             UnpackExtraDataFailed => 14,
-            InvalidAccountOwner => 14,
-            DeserializeStorageFailed => 15,
-            AlreadyInitialized => 16,
-            WritableHistoryDuringProofCheck => 17,
-
-            InvalidProof_BadBlockHash => 18,
-            InvalidProof_TooEasy => 19,
-            InvalidProof_BadMerkle => 20,
-
-            InvalidChallenge_BadBlockHash => 21,
-            InvalidChallenge_InvalidIndex => 22,
-            InvalidChallenge_BadMerkleProof => 23,
-            InvalidChallenge_BadMerkleRoot => 24,
-            InvalidChallenge_SameElement => 25,
-
-            ContractIsDead => 26,
-            EthashElementsForWrongBlock => 27,
-            EthashElementRewriting => 28,
+            InvalidAccountOwner => 15,
+            DeserializeStorageFailed => 16,
+            AlreadyInitialized => 17,
+            WritableHistoryDuringProofCheck => 18,
+
+            InvalidProof_BadBlockHash => 19,
+            InvalidProof_TooEasy => 20,
+            InvalidProof_BadMerkle => 21,
+
+            InvalidChallenge_BadBlockHash => 22,
+            InvalidChallenge_InvalidIndex => 23,
+            InvalidChallenge_BadMerkleProof => 24,
+            InvalidChallenge_BadMerkleRoot => 25,
+            InvalidChallenge_SameElement => 26,
+
+            ContractIsDead => 27,
+            EthashElementsForWrongBlock => 28,
+            EthashElementRewriting => 29,
+
+            VerifyHeaderFailed_InvalidDifficulty => 30,
+            InvalidReplacement_NotHeavier => 31,
+            DecodeTxProofInstructionFailed(_) => 32,
+            DecodeReceiptProofInstructionFailed(_) => 33,
+            DecodeStorageProofInstructionFailed(_) => 34,
+            VerifyHeaderFailed_TooFarInFuture => 35,
+            VerifyHeaderFailed_InvalidGasLimit => 36,
+            OutputAccountTooSmall => 37,
+            PowElementsPending => 38,
+            VerifyHeaderFailed_InvalidSeal => 39,
+            InvalidCheckpoint => 40,
+            HeaderNotConfirmed => 41,
+            InvalidPrune => 42,
+            NoCandidateTip => 43,
+            WrongNetwork => 44,
+            InvalidSignature => 45,
+            InvalidRollback => 46,
+            VerifyHeaderFailed_CheckpointMismatch => 47,
+            VerifyHeaderFailed_InvalidProofOfStake => 48,
+            VerifyHeaderFailed_BelowMinDifficulty => 49,
+            CorruptStorage => 50,
+            VerifyHeaderFailed_HardforkRule => 51,
+            ReorgTooDeep => 52,
+            EthashDisabled => 53,
+            VerifyHeaderFailed_GasUsedOverLimit => 54,
+            InvalidNextAccount => 55,
+            StorageRolledOver => 56,
+            NoCacheRoot => 57,
+            VerifyHeaderFailed_RecentlySigned => 58,
         })
     }
 }
+
+impl From<BridgeError> for ProgramError {
+    fn from(e: BridgeError) -> Self {
+        e.to_program_error()
+    }
+}